@@ -0,0 +1,61 @@
+//! Colors are rendered as linear floats where 1.0 is the brightest value of an 8 bit channel,
+//! they are only converted back to 8 bits when the image is shown or saved, see `tone_map::DisplayTransform`
+
+use std::sync::OnceLock;
+
+use crate::vector::Point;
+
+/// 8 bit sRGB color from a scene file or texture to linear 0..1
+pub fn from_u8(color: Point<u8>) -> Point<f32> {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| std::array::from_fn(|i| srgb_decode(i as f32 / 255.0)));
+    Point::new(
+        table[color.x as usize],
+        table[color.y as usize],
+        table[color.z as usize],
+    )
+}
+
+/// Clips everything outside 0..1
+pub fn quantize(color: Point<f32>) -> Point<u8> {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Point::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+/// Linear to the sRGB transfer curve, for 0..1
+pub fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        return c * 12.92;
+    }
+    return 1.055 * c.powf(1.0 / 2.4) - 0.055;
+}
+
+/// sRGB transfer curve to linear, for 0..1
+pub fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        return c / 12.92;
+    }
+    return ((c + 0.055) / 1.055).powf(2.4);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_u8, quantize, srgb_encode};
+    use crate::vector::Point;
+
+    #[test]
+    fn round_trip() {
+        for c in 0..=255 {
+            let linear = from_u8(Point::homogeneous(c));
+            let encoded = Point::new(srgb_encode(linear.x), srgb_encode(linear.y), srgb_encode(linear.z));
+            assert_eq!(quantize(encoded), Point::homogeneous(c));
+        }
+        assert_eq!(quantize(Point::new(-1.0, 0.5, 7.0)), Point::new(0, 128, 255));
+    }
+
+    #[test]
+    fn mid_gray_is_darker_in_linear() {
+        let linear = from_u8(Point::homogeneous(128)).x;
+        assert!((linear - 0.2158605).abs() < 1e-5);
+    }
+}
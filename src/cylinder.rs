@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+use crate::num::PositiveNonzeroF32;
+use crate::octree::AABB;
+use crate::util::{correct_normal, Hit, Intersect, Ray, Shape, RAY_EPSILON};
+use crate::vector::Point;
+
+/// Cylinder closed by a cap on both ends, `center` is halfway between the caps
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cylinder {
+    pub center: Point<f32>,
+    pub axis: Point<f32>,
+    pub radius: f32,
+    pub height: f32,
+    /// Index into `Scene.materials`
+    pub material: usize,
+}
+
+impl Cylinder {
+    pub fn new(
+        center: Point<f32>,
+        axis: Point<f32>,
+        diameter: PositiveNonzeroF32,
+        height: PositiveNonzeroF32,
+        material: usize,
+    ) -> Self {
+        Self {
+            center,
+            axis: axis.to_normalized(),
+            radius: diameter.get() / 2.0,
+            height: height.get(),
+            material,
+        }
+    }
+
+    /// Distance to and outward normal of the closest hit on the curved surface
+    fn hit_body(&self, ray: &Ray) -> Option<(f32, Point<f32>)> {
+        let oc = ray.origin - self.center;
+        // project everything onto the plane perpendicular to the axis, where the body is a circle
+        let dir_perp = ray.dir - self.axis * ray.dir.dot(&self.axis);
+        let oc_perp = oc - self.axis * oc.dot(&self.axis);
+
+        let a = dir_perp.length2();
+        if a < f32::EPSILON {
+            return None; // Ray is parallel to the axis
+        }
+        let b = 2.0 * dir_perp.dot(&oc_perp);
+        let c = oc_perp.length2() - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt = discriminant.sqrt();
+
+        for t in [(-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a)] {
+            if t < RAY_EPSILON {
+                continue;
+            }
+            let local = oc + ray.dir * t;
+            let along_axis = local.dot(&self.axis);
+            if along_axis.abs() <= self.height / 2.0 {
+                let normal = (local - self.axis * along_axis) / self.radius;
+                return Some((t, normal));
+            }
+        }
+        None
+    }
+
+    /// Distance to and outward normal of the closest hit on one of the caps
+    fn hit_caps(&self, ray: &Ray) -> Option<(f32, Point<f32>)> {
+        let denominator = ray.dir.dot(&self.axis);
+        if denominator.abs() < f32::EPSILON {
+            return None; // Ray is parallel to the caps
+        }
+        let mut closest: Option<(f32, Point<f32>)> = None;
+
+        for normal in [self.axis, -self.axis] {
+            let cap_center = self.center + normal * (self.height / 2.0);
+            let t = (cap_center - ray.origin).dot(&self.axis) / denominator;
+            if t < RAY_EPSILON {
+                continue;
+            }
+            let point = ray.origin + ray.dir * t;
+            if point.distance2(&cap_center) > self.radius * self.radius {
+                continue;
+            }
+            if closest.is_none_or(|(closest, _)| t < closest) {
+                closest = Some((t, normal));
+            }
+        }
+        closest
+    }
+}
+
+impl Intersect for Cylinder {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let (t, normal) = match (self.hit_body(ray), self.hit_caps(ray)) {
+            (Some(body), Some(cap)) => {
+                if body.0 < cap.0 {
+                    body
+                } else {
+                    cap
+                }
+            }
+            (Some(body), None) => body,
+            (None, Some(cap)) => cap,
+            (None, None) => return None,
+        };
+        let (normal, front_face) = correct_normal(normal, &ray.dir);
+        Some(Hit::new(
+            t,
+            ray.origin,
+            ray.origin + ray.dir * t,
+            normal,
+            front_face,
+            self.material,
+        ))
+    }
+}
+
+impl Shape for Cylinder {
+    fn is_inside_aabb(&self, aabb: &AABB) -> bool {
+        let (min, max) = self.extent();
+        aabb.is_inside_all(&[min, max])
+    }
+
+    fn aabb(&self) -> AABB {
+        let (min, max) = self.extent();
+        AABB::new(min, max)
+    }
+}
+
+impl Cylinder {
+    /// Exact bounds: the caps are discs, which stick out `radius * sin(angle to the world axis)` along each axis
+    fn extent(&self) -> (Point<f32>, Point<f32>) {
+        let disc = |a: f32| self.radius * (1.0 - a * a).max(0.0).sqrt();
+        let half = self.axis * (self.height / 2.0);
+        let extent = Point::new(
+            half.x.abs() + disc(self.axis.x),
+            half.y.abs() + disc(self.axis.y),
+            half.z.abs() + disc(self.axis.z),
+        );
+        (self.center - extent, self.center + extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cylinder::Cylinder;
+    use crate::num::PositiveNonzeroF32;
+    use crate::util::{Intersect, Ray, Shape};
+    use crate::vector::Point;
+
+    // radius 1, from y = -1 to y = 1
+    fn cylinder() -> Cylinder {
+        Cylinder::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            PositiveNonzeroF32::new(2.0).unwrap(),
+            PositiveNonzeroF32::new(2.0).unwrap(),
+            0,
+        )
+    }
+
+    #[test]
+    fn hit_body() {
+        let ray = Ray::new(Point::new(0.0, 0.5, -5.0), Point::new(0.0, 0.0, 1.0));
+        let hit = cylinder().hit(&ray).unwrap();
+        assert!((hit.dist - 4.0).abs() < 1e-5);
+        assert!(hit.normal.approx_eq(&Point::new(0.0, 0.0, -1.0), 1e-5));
+    }
+
+    #[test]
+    fn hit_cap() {
+        let ray = Ray::new(Point::new(0.5, 5.0, 0.0), Point::new(0.0, -1.0, 0.0));
+        let hit = cylinder().hit(&ray).unwrap();
+        assert!((hit.dist - 4.0).abs() < 1e-5);
+        assert!(hit.normal.approx_eq(&Point::new(0.0, 1.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn miss() {
+        let above = Ray::new(Point::new(0.0, 1.5, -5.0), Point::new(0.0, 0.0, 1.0));
+        assert!(cylinder().hit(&above).is_none());
+
+        let beside = Ray::new(Point::new(1.5, 5.0, 0.0), Point::new(0.0, -1.0, 0.0));
+        assert!(cylinder().hit(&beside).is_none());
+    }
+
+    #[test]
+    fn aabb() {
+        let aabb = cylinder().aabb();
+        assert!(aabb.is_inside(&Point::new(1.0, 1.0, 1.0)));
+        assert!(aabb.is_inside(&Point::new(-1.0, -1.0, -1.0)));
+        assert!(!aabb.is_inside(&Point::new(0.0, 1.1, 0.0)));
+    }
+}
@@ -1,6 +1,8 @@
+use crate::image_format::ImageFormat;
 use crate::num::Float0to1;
 use crate::random_iterator::RandomIterator;
 use crate::resolution::Resolution;
+use crate::tone_map::DisplayTransform;
 use crate::util::{PixelReq, PixelReqBuffer, PixelResBuffer, PIXEL_BUFFER_SIZE};
 use crate::vector::Point;
 use bitvec::prelude::*;
@@ -20,16 +22,20 @@ pub fn to_u8(color: u32) -> Point<u8> {
     )
 }
 
+/// Hands out every pixel of every camera in `cameras` once
 pub struct PixelProvider {
     pixel_index: RandomIterator,
     resolution: Resolution,
+    cameras: Vec<usize>,
 }
 
 impl PixelProvider {
-    pub fn new(resolution: &Resolution) -> PixelProvider {
+    pub fn new(resolution: &Resolution, cameras: Vec<usize>, seed: u64) -> PixelProvider {
+        let pixels = resolution.width.get() * resolution.height.get() * cameras.len();
         PixelProvider {
-            pixel_index: RandomIterator::new(resolution.width.get() * resolution.height.get()),
+            pixel_index: RandomIterator::with_seed(pixels, seed),
             resolution: *resolution,
+            cameras,
         }
     }
 
@@ -64,7 +70,10 @@ impl PixelProvider {
     }
 
     fn i_to_coord(&self, i: usize) -> PixelReq {
-        PixelReq::new(i % self.resolution.width.get(), i / self.resolution.width.get())
+        let pixels = self.resolution.width.get() * self.resolution.height.get();
+        let camera = self.cameras[i / pixels];
+        let i = i % pixels;
+        PixelReq::new(i % self.resolution.width.get(), i / self.resolution.width.get(), camera)
     }
 }
 
@@ -82,8 +91,9 @@ pub enum Flip {
     Vertical,
 }
 
+/// Holds linear colors, they go through a `DisplayTransform` when the buffer is shown or saved
 pub struct FrameBuffer {
-    buffer: Vec<u32>,
+    buffer: Vec<Point<f32>>,
     assigned_pixels: BitVec<u32, Lsb0>,
     resolution: Resolution,
 }
@@ -91,8 +101,11 @@ pub struct FrameBuffer {
 impl FrameBuffer {
     // ? why am I allowed to use a result when there is only ever a ok?
     pub fn new(resolution: &Resolution) -> Result<FrameBuffer, &'static str> {
-        let mut buffer = Vec::<u32>::new();
-        buffer.resize(resolution.width.get() * resolution.height.get(), 0);
+        let mut buffer = Vec::<Point<f32>>::new();
+        buffer.resize(
+            resolution.width.get() * resolution.height.get(),
+            Point::homogeneous(0.0),
+        );
         return Ok(FrameBuffer {
             buffer,
             resolution: *resolution,
@@ -100,6 +113,11 @@ impl FrameBuffer {
         });
     }
 
+    /// 0RGB pixels row by row, the layout minifb expects
+    pub fn to_u32_buffer(&self, display: &DisplayTransform) -> Vec<u32> {
+        self.buffer.iter().map(|color| to_u32(display.apply(*color))).collect()
+    }
+
     pub fn pixel_count(&self) -> usize {
         return self.resolution.width.get() * self.resolution.height.get();
     }
@@ -113,12 +131,12 @@ impl FrameBuffer {
         self.assigned_pixels.iter().all(|x| *x)
     }
 
-    pub fn set_pixel(&mut self, x: usize, y: usize, color: Point<u8>) {
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Point<f32>) {
         let i = x + y * self.resolution.width.get();
         if i >= self.buffer.len() {
             panic!("Index out of bounds");
         }
-        self.buffer[i] = to_u32(color);
+        self.buffer[i] = color;
         self.assigned_pixels.set(i, true);
     }
 
@@ -140,17 +158,14 @@ impl FrameBuffer {
         }
     }
 
-    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Point<u8>> {
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Point<f32>> {
         let i = x + y * self.resolution.width.get();
         if i >= self.buffer.len() {
             return None;
         }
-        return Some(to_u8(self.buffer[i]));
+        return Some(self.buffer[i]);
     }
 
-    fn i_to_coord(&self, i: usize) -> PixelReq {
-        PixelReq::new(i % self.resolution.width.get(), i / self.resolution.width.get())
-    }
     fn coord_to_i(&self, x: usize, y: usize) -> usize {
         y * self.resolution.width.get() + x
     }
@@ -181,13 +196,28 @@ impl FrameBuffer {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn save_as_bmp(&self, path: &std::path::Path) -> io::Result<()> {
+    /// The encoder is picked by the extension of `path`.
+    /// Float formats get the linear colors, the others go through `display`
+    pub fn save(&self, path: &std::path::Path, display: &DisplayTransform) -> Result<(), String> {
+        let format = ImageFormat::from_path(path)?;
+        let width = self.resolution.width.get();
+        let height = self.resolution.height.get();
+        match format {
+            ImageFormat::Bmp => self.save_as_bmp(path, display).map_err(|e| e.to_string()),
+            _ if format.is_float() => format.save_float(path, width, height, &self.buffer),
+            _ => {
+                let pixels: Vec<Point<u8>> = self.buffer.iter().map(|color| display.apply(*color)).collect();
+                format.save_8bit(path, width, height, &pixels)
+            }
+        }
+    }
+
+    pub fn save_as_bmp(&self, path: &std::path::Path, display: &DisplayTransform) -> io::Result<()> {
         let mut img = Image::new(self.resolution.width.get() as u32, self.resolution.height.get() as u32);
 
         for x in 0..self.resolution.width.get() {
             for y in 0..self.resolution.height.get() {
-                let color = self.get_pixel(x, y).unwrap();
+                let color = display.apply(self.get_pixel(x, y).unwrap());
                 img.set_pixel(x as u32, y as u32, Pixel::new(color.x, color.y, color.z));
             }
         }
@@ -195,3 +225,74 @@ impl FrameBuffer {
         return img.save(path);
     }
 }
+
+/// One frame buffer per rendered camera, pixels are routed by `PixelRes.camera`
+pub struct CameraFrameBuffers {
+    frame_buffers: Vec<(usize, FrameBuffer)>,
+}
+
+impl CameraFrameBuffers {
+    pub fn new(resolution: &Resolution, cameras: &[usize]) -> Self {
+        Self {
+            frame_buffers: cameras
+                .iter()
+                .map(|camera| (*camera, FrameBuffer::new(resolution).unwrap()))
+                .collect(),
+        }
+    }
+
+    pub fn get_mut(&mut self, camera: usize) -> Option<&mut FrameBuffer> {
+        self.frame_buffers
+            .iter_mut()
+            .find(|(c, _)| *c == camera)
+            .map(|(_, frame_buffer)| frame_buffer)
+    }
+
+    pub fn set_pixel_from_buffer(&mut self, buffer: &PixelResBuffer) {
+        for pixel in buffer.iter().flatten() {
+            match self.get_mut(pixel.camera) {
+                Some(frame_buffer) => frame_buffer.set_pixel(pixel.x, pixel.y, pixel.color),
+                None => println!("WARN: received pixel for camera {} which is not rendered", pixel.camera),
+            }
+        }
+    }
+
+    pub fn set_pixel_from_iterator(&mut self, iter: &mut impl Iterator<Item = PixelResBuffer>) {
+        for pixel_buffer in iter {
+            self.set_pixel_from_buffer(&pixel_buffer);
+        }
+    }
+
+    pub fn progress(&self) -> Float0to1 {
+        let total: f32 = self.frame_buffers.iter().map(|(_, fb)| fb.progress().get()).sum();
+        return Float0to1::new(total / self.frame_buffers.len() as f32).unwrap();
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.frame_buffers
+            .iter()
+            .all(|(_, frame_buffer)| frame_buffer.is_complete())
+    }
+
+    /// With more than one camera, the camera index is appended to the file name: output.png -> output_2.png
+    pub fn save(&self, path: &std::path::Path, display: &DisplayTransform) -> Result<(), String> {
+        if self.frame_buffers.len() == 1 {
+            return self.frame_buffers[0].1.save(path, display);
+        }
+        for (camera, frame_buffer) in &self.frame_buffers {
+            let path = numbered_path(path, *camera);
+            frame_buffer.save(&path, display)?;
+            println!("Saved camera {} to {}", camera, path.display());
+        }
+        Ok(())
+    }
+}
+
+fn numbered_path(path: &std::path::Path, n: usize) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}_{n}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{n}"),
+    };
+    path.with_file_name(file_name)
+}
@@ -84,3 +84,15 @@ where
     }
     return false;
 }
+
+/// A new directory for the files of one test, tests running in parallel or in other processes never share one
+#[cfg(test)]
+pub fn test_dir(name: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos();
+    let dir = std::env::temp_dir().join(format!("minirt_{}_{}_{}", name, std::process::id(), nanos));
+    std::fs::create_dir_all(&dir).unwrap();
+    return dir;
+}
@@ -0,0 +1,151 @@
+use image::codecs::hdr::HdrEncoder;
+use image::{Rgb, Rgb32FImage, RgbImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::vector::Point;
+
+/// Formats the rendered image can be saved as, picked by the extension of the output path
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    Ppm,
+    Tga,
+    /// Portable float map, the float variant of PPM
+    Pfm,
+    Exr,
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match extension.to_lowercase().as_str() {
+            "bmp" => Ok(ImageFormat::Bmp),
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "tga" => Ok(ImageFormat::Tga),
+            "pfm" => Ok(ImageFormat::Pfm),
+            "exr" => Ok(ImageFormat::Exr),
+            "hdr" => Ok(ImageFormat::Hdr),
+            _ => Err(format!(
+                "{}: unknown image format, expected .bmp, .png, .ppm, .tga, .pfm, .exr or .hdr",
+                path.display()
+            )),
+        }
+    }
+
+    /// Float formats store the pixels as they are, the others as 8 bits per channel
+    pub fn is_float(&self) -> bool {
+        matches!(self, ImageFormat::Pfm | ImageFormat::Exr | ImageFormat::Hdr)
+    }
+
+    /// `pixels` are row by row, top row first. BMP is written by `FrameBuffer::save_as_bmp`
+    pub fn save_8bit(&self, path: &Path, width: usize, height: usize, pixels: &[Point<u8>]) -> Result<(), String> {
+        let format = match self {
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Ppm => image::ImageFormat::Pnm,
+            ImageFormat::Tga => image::ImageFormat::Tga,
+            _ => return Err(format!("{:?} is not an 8 bit format", self)),
+        };
+        let image = RgbImage::from_fn(width as u32, height as u32, |x, y| {
+            let color = pixels[x as usize + y as usize * width];
+            Rgb([color.x, color.y, color.z])
+        });
+        image.save_with_format(path, format).map_err(|e| e.to_string())
+    }
+
+    /// `pixels` are row by row, top row first
+    pub fn save_float(&self, path: &Path, width: usize, height: usize, pixels: &[Point<f32>]) -> Result<(), String> {
+        match self {
+            ImageFormat::Exr => {
+                let image = Rgb32FImage::from_fn(width as u32, height as u32, |x, y| {
+                    let color = pixels[x as usize + y as usize * width];
+                    Rgb([color.x, color.y, color.z])
+                });
+                image
+                    .save_with_format(path, image::ImageFormat::OpenExr)
+                    .map_err(|e| e.to_string())
+            }
+            ImageFormat::Hdr => {
+                let file = File::create(path).map_err(|e| e.to_string())?;
+                let pixels: Vec<Rgb<f32>> = pixels.iter().map(|c| Rgb([c.x, c.y, c.z])).collect();
+                HdrEncoder::new(BufWriter::new(file))
+                    .encode(&pixels, width, height)
+                    .map_err(|e| e.to_string())
+            }
+            ImageFormat::Pfm => write_pfm(path, width, height, pixels).map_err(|e| e.to_string()),
+            _ => Err(format!("{:?} is not a float format", self)),
+        }
+    }
+}
+
+/// Header, then little endian RGB floats with the bottom row first
+fn write_pfm(path: &Path, width: usize, height: usize, pixels: &[Point<f32>]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    // a negative scale means little endian
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+    for y in (0..height).rev() {
+        for color in &pixels[y * width..(y + 1) * width] {
+            for channel in [color.x, color.y, color.z] {
+                file.write_all(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageFormat;
+    use crate::helpers::test_dir;
+    use crate::vector::Point;
+    use image::codecs::hdr::HdrDecoder;
+    use std::path::Path;
+
+    #[test]
+    fn from_path() {
+        assert_eq!(ImageFormat::from_path(Path::new("out.PNG")), Ok(ImageFormat::Png));
+        assert_eq!(
+            ImageFormat::from_path(Path::new("dir.v2/out.exr")),
+            Ok(ImageFormat::Exr)
+        );
+        assert!(ImageFormat::from_path(Path::new("out.jpg")).is_err());
+        assert!(ImageFormat::from_path(Path::new("out")).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = test_dir("round_trip");
+        let pixels = vec![Point::new(255, 0, 0), Point::new(0, 255, 0)];
+        for format in ["png", "ppm", "tga"] {
+            let path = dir.join(format!("round_trip.{format}"));
+            let image_format = ImageFormat::from_path(&path).unwrap();
+            image_format.save_8bit(&path, 2, 1, &pixels).unwrap();
+            let image = image::open(&path).unwrap().to_rgb8();
+            assert_eq!(image.get_pixel(1, 0).0, [0, 255, 0], "{format}");
+        }
+
+        let pixels = vec![Point::new(4.0, 0.5, 0.0), Point::new(0.0, 0.0, 1.0)];
+        let path = dir.join("round_trip.exr");
+        ImageFormat::Exr.save_float(&path, 1, 2, &pixels).unwrap();
+        let image = image::open(&path).unwrap().to_rgb32f();
+        assert_eq!(image.get_pixel(0, 0).0, [4.0, 0.5, 0.0]);
+
+        // image::open would convert HDR to 8 bits
+        let path = dir.join("round_trip.hdr");
+        ImageFormat::Hdr.save_float(&path, 1, 2, &pixels).unwrap();
+        let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+        let image = HdrDecoder::new(file).unwrap().read_image_hdr().unwrap();
+        assert_eq!(image[0].0, [4.0, 0.5, 0.0]);
+
+        let path = dir.join("round_trip.pfm");
+        ImageFormat::Pfm.save_float(&path, 1, 2, &pixels).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"PF\n1 2\n-1.0\n"));
+        assert_eq!(bytes.len(), 12 + 2 * 3 * 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,31 +1,74 @@
 use crate::{
+    image_format::ImageFormat,
     resolution::{AALevel, Resolution},
-    scene_readers::{read_scene, Scene},
+    scene_readers::{read_scene, ParseMode, Scene},
+    tone_map::{DisplayTransform, ToneMap},
 };
 use std::str::FromStr;
-use std::{
-    num::NonZeroUsize,
-    path::{Path, PathBuf},
-};
+use std::{num::NonZeroUsize, path::PathBuf};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter, EnumString};
 
-pub fn get_scene(path: &Path) -> Result<Scene, String> {
-    let scene = read_scene(&path).unwrap();
+/// Reads the input file, the render settings given on the command line override the ones of the scene
+pub fn get_scene(argv: &Argv) -> Result<Scene, String> {
+    let mut scene = read_scene(argv.input_file.as_ref().unwrap(), argv.parse_mode)?;
+    if let Some(max_depth) = argv.max_depth {
+        scene.settings.max_depth = max_depth;
+    }
+    if let Some(light_samples) = argv.light_samples {
+        scene.settings.light_samples = light_samples.get();
+    }
     scene.print_stats();
     Ok(scene)
 }
 
-pub fn get_resolution() -> Resolution {
+/// Command line overrides win over the `R` line of the scene, which wins over the default
+pub fn get_resolution(scene: &Scene, argv: &Argv) -> Resolution {
+    let from_scene = scene.resolution.unwrap_or_default();
     let resolution = Resolution::new(
-        NonZeroUsize::new(50).unwrap(),
-        NonZeroUsize::new(50).unwrap(),
-        AALevel::new(1).unwrap(),
+        argv.width.unwrap_or(from_scene.width),
+        argv.height.unwrap_or(from_scene.height),
+        argv.aa.unwrap_or(from_scene.aa),
     );
     resolution.print();
     resolution
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CameraSelection {
+    Index(usize),
+    All,
+}
+
+impl FromStr for CameraSelection {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(CameraSelection::All),
+            _ => s.parse::<usize>().map(CameraSelection::Index).map_err(|_| ()),
+        }
+    }
+}
+
+impl CameraSelection {
+    /// Indices into `Scene.cameras`
+    pub fn indices(&self, scene: &Scene) -> Result<Vec<usize>, String> {
+        let count = scene.cameras.len();
+        if count == 0 {
+            return Err("The scene has no cameras".into());
+        }
+        match *self {
+            CameraSelection::All => Ok((0..count).collect()),
+            CameraSelection::Index(i) if i < count => Ok(vec![i]),
+            CameraSelection::Index(i) => Err(format!(
+                "Camera {i} does not exist, the scene has {count} camera(s): 0..{}",
+                count - 1
+            )),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Display, EnumString, EnumIter)]
 pub enum Mode {
     ToFile,
@@ -37,55 +80,285 @@ pub enum Mode {
 #[derive(Debug)]
 pub struct Argv {
     pub mode: Mode,
-    pub input_file: PathBuf,
+    /// Only optional for `NetClient`, which receives the scene from the server
+    pub input_file: Option<PathBuf>,
     pub output_file: Option<PathBuf>,
     pub address: Option<String>,
+    pub width: Option<NonZeroUsize>,
+    pub height: Option<NonZeroUsize>,
+    pub aa: Option<AALevel>,
+    pub threads: Option<NonZeroUsize>,
+    pub max_depth: Option<usize>,
+    pub light_samples: Option<NonZeroUsize>,
+    pub seed: u64,
+    pub display: DisplayTransform,
+    pub parse_mode: ParseMode,
+    pub camera: CameraSelection,
 }
 
 impl Argv {
+    /// Parses the process arguments, prints the usage and exits when they are invalid
     pub fn new() -> Self {
         let argv = std::env::args().collect::<Vec<_>>();
-        if argv.len() <= 1 {
-            error(&argv);
+        let program = argv.get(0).map(|s| s.as_str()).unwrap_or("minirt_rs");
+        if argv.iter().skip(1).any(|arg| arg == "--help" || arg == "-h") {
+            println!("{}", usage(program));
+            std::process::exit(0);
         }
-        let mode = Mode::from_str(argv.get(1).unwrap());
-        if mode.is_err() {
-            error(&argv);
-        }
-        let mode = mode.unwrap();
-
-        if mode == Mode::NetClient || mode == Mode::NetServer {
-            if argv.len() < 5 {
-                error(&argv);
+        match Self::parse(&argv[1.min(argv.len())..]) {
+            Ok(argv) => argv,
+            Err(e) => {
+                eprintln!("Error: {e}\n\n{}", usage(program));
+                std::process::exit(1);
             }
-            let input_file = argv.get(2).map(|s| PathBuf::from(s)).unwrap();
-            let output_file = argv.get(3).map(|s| PathBuf::from(s));
-            let address = argv.get(4).map(|s| s.to_string());
-            return Self {
-                mode,
-                input_file,
-                address,
-                output_file,
-            };
         }
+    }
 
-        let input_file = argv.get(2).map(|s| PathBuf::from(s)).unwrap();
-        let output_file = argv.get(3).map(|s| PathBuf::from(s));
+    /// `args` excludes the program name
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut args = args.iter();
+        let mode = args.next().ok_or("No mode given")?;
+        let mode = Mode::from_str(mode).map_err(|_| format!("Unknown mode '{mode}', expected one of: {}", modes()))?;
 
-        Self {
+        let mut argv = Self {
             mode,
-            input_file,
-            output_file,
+            input_file: None,
+            output_file: None,
             address: None,
+            width: None,
+            height: None,
+            aa: None,
+            threads: None,
+            max_depth: None,
+            light_samples: None,
+            seed: 0,
+            display: DisplayTransform::default(),
+            parse_mode: ParseMode::Lenient,
+            camera: CameraSelection::Index(0),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--output" | "-o" => argv.output_file = Some(PathBuf::from(value()?)),
+                "--address" => argv.address = Some(value()?.to_string()),
+                "--width" => argv.width = Some(parse_value(arg, value()?, NonZeroUsize::new, "a positive number")?),
+                "--height" => argv.height = Some(parse_value(arg, value()?, NonZeroUsize::new, "a positive number")?),
+                "--aa" => argv.aa = Some(parse_value(arg, value()?, AALevel::new, "1, 4, 16 or 64")?),
+                "--threads" => argv.threads = Some(parse_value(arg, value()?, NonZeroUsize::new, "a positive number")?),
+                "--max-depth" => argv.max_depth = Some(parse_value(arg, value()?, Some, "a number")?),
+                "--light-samples" => {
+                    argv.light_samples = Some(parse_value(arg, value()?, NonZeroUsize::new, "a positive number")?)
+                }
+                "--seed" => {
+                    let seed = value()?;
+                    argv.seed = seed
+                        .parse()
+                        .map_err(|_| format!("Invalid value '{seed}' for {arg}, expected a number"))?;
+                }
+                "--camera" => {
+                    let camera = value()?;
+                    argv.camera = CameraSelection::from_str(camera)
+                        .map_err(|_| format!("Invalid value '{camera}' for {arg}, expected a camera index or 'all'"))?;
+                }
+                "--tone-map" => {
+                    let tone_map = value()?;
+                    argv.display.tone_map = ToneMap::from_str(tone_map).map_err(|_| {
+                        let tone_maps = ToneMap::iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ");
+                        format!("Invalid value '{tone_map}' for {arg}, expected one of: {tone_maps}")
+                    })?;
+                }
+                "--exposure" => {
+                    let exposure = value()?;
+                    argv.display.exposure = exposure.parse::<f32>().ok().filter(|e| e.is_finite()).ok_or(format!(
+                        "Invalid value '{exposure}' for {arg}, expected a number of stops"
+                    ))?;
+                }
+                "--linear" => argv.display.srgb = false,
+                "--strict" => argv.parse_mode = ParseMode::Strict,
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+                _ if argv.input_file.is_none() => argv.input_file = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument '{arg}'")),
+            }
+        }
+
+        argv.validate()?;
+        Ok(argv)
+    }
+
+    /// Checks that everything the mode needs is there
+    fn validate(&self) -> Result<(), String> {
+        let needs_input = self.mode != Mode::NetClient;
+        let needs_output = self.mode == Mode::ToFile || self.mode == Mode::NetServer;
+        let needs_address = self.mode == Mode::NetClient || self.mode == Mode::NetServer;
+
+        match &self.input_file {
+            None if needs_input => return Err(format!("{} needs a scene file", self.mode)),
+            Some(path) if !path.is_file() => return Err(format!("Scene file {} does not exist", path.display())),
+            _ => (),
+        }
+        match &self.output_file {
+            None if needs_output => return Err(format!("{} needs an output file, pass it with --output", self.mode)),
+            // checked before rendering instead of after
+            Some(path) if needs_output => _ = ImageFormat::from_path(path)?,
+            Some(path) if self.mode == Mode::Window && path.extension().is_none_or(|e| e != "cbor") => {
+                return Err(format!(
+                    "Window saves the scene to --output, {} must end with .cbor",
+                    path.display()
+                ))
+            }
+            _ => (),
         }
+        if needs_address && self.address.is_none() {
+            return Err(format!("{} needs a server address, pass it with --address", self.mode));
+        }
+        Ok(())
     }
 }
 
-fn error(argv: &Vec<String>) -> ! {
-    let modes = Mode::iter().map(|m| m.to_string()).collect::<Vec<_>>().join(", ");
-    println!(
-        "Usage: {} <{modes}> <scene.[rt,obj,blend]> <output_file.[bmp,cbor]> [<address>]",
-        argv.get(0).unwrap()
-    );
-    std::process::exit(1);
+fn parse_value<T>(
+    flag: &str,
+    value: &str,
+    constructor: impl Fn(usize) -> Option<T>,
+    expected: &str,
+) -> Result<T, String> {
+    value
+        .parse::<usize>()
+        .ok()
+        .and_then(constructor)
+        .ok_or(format!("Invalid value '{value}' for {flag}, expected {expected}"))
+}
+
+fn modes() -> String {
+    Mode::iter().map(|m| m.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn usage(program: &str) -> String {
+    format!(
+        "Usage: {program} <mode> [<scene.[rt,obj,cbor]>] [options]
+
+Modes: {}
+  ToFile     render the scene to --output
+  Window     render the scene in a window
+  NetServer  distribute the scene over the clients connecting to --address and save to --output
+  NetClient  render for the server at --address, the scene is received from the server
+
+Options:
+  -o, --output <file>      where the image is saved as .bmp, .png, .ppm, .tga, .pfm, .exr or .hdr,
+                           in Window mode where E saves the scene (.cbor)
+  --address <host:port>    address of the server
+  --width <px>             overrides the resolution of the scene
+  --height <px>
+  --aa <1,4,16,64>         anti-aliasing samples per pixel
+  --threads <n>            render threads, defaults to the number of cores
+  --max-depth <n>          maximum bounces of reflected and refracted rays, defaults to 5
+  --light-samples <n>      shadow rays per area light, rounded down to a square, defaults to 16
+  --camera <index|all>     camera to render, defaults to 0
+  --seed <n>               seed for the order in which pixels are rendered, defaults to 0
+  --tone-map <mode>        clamp (default), reinhard or aces, for everything except .exr, .hdr and .pfm
+  --exposure <stops>       brightens or darkens before tone mapping, defaults to 0
+  --linear                 skip the sRGB encoding after tone mapping
+  --strict                 fail on the first malformed line of an .rt scene instead of skipping it
+  -h, --help               print this message",
+        modes()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Argv, CameraSelection, Mode};
+    use crate::scene_readers::test_scene;
+    use crate::tone_map::{DisplayTransform, ToneMap};
+    use std::path::PathBuf;
+
+    fn parse(args: &str) -> Result<Argv, String> {
+        Argv::parse(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn flags() {
+        let argv =
+            parse("ToFile rt/standard.rt --width 640 --height 480 --aa 4 --threads 3 --camera all --seed 7 -o out.bmp --max-depth 2 --light-samples 4")
+                .unwrap();
+        assert_eq!(argv.mode, Mode::ToFile);
+        assert_eq!(argv.input_file, Some(PathBuf::from("rt/standard.rt")));
+        assert_eq!(argv.output_file, Some(PathBuf::from("out.bmp")));
+        assert_eq!(argv.width.unwrap().get(), 640);
+        assert_eq!(argv.height.unwrap().get(), 480);
+        assert_eq!(argv.aa.unwrap().get(), 4);
+        assert_eq!(argv.threads.unwrap().get(), 3);
+        assert_eq!(argv.camera, CameraSelection::All);
+        assert_eq!(argv.seed, 7);
+        assert_eq!(argv.max_depth, Some(2));
+        assert_eq!(argv.light_samples.unwrap().get(), 4);
+        assert_eq!(argv.display, DisplayTransform::default());
+
+        let argv = parse("ToFile rt/standard.rt -o out.png --tone-map aces --exposure -1.5 --linear").unwrap();
+        assert_eq!(argv.display.tone_map, ToneMap::Aces);
+        assert_eq!(argv.display.exposure, -1.5);
+        assert!(!argv.display.srgb);
+    }
+
+    #[test]
+    fn camera_indices() {
+        let scene = |cameras| {
+            let mut scene = test_scene(vec![], vec![], vec![]);
+            scene.cameras = vec![scene.cameras[0].clone(); cameras];
+            scene
+        };
+        assert_eq!(CameraSelection::All.indices(&scene(3)).unwrap(), vec![0, 1, 2]);
+        assert_eq!(CameraSelection::Index(1).indices(&scene(3)).unwrap(), vec![1]);
+        assert!(CameraSelection::Index(3)
+            .indices(&scene(3))
+            .unwrap_err()
+            .contains("0..2"));
+        assert!(CameraSelection::All
+            .indices(&scene(0))
+            .unwrap_err()
+            .contains("no cameras"));
+        assert!(CameraSelection::Index(0)
+            .indices(&scene(0))
+            .unwrap_err()
+            .contains("no cameras"));
+    }
+
+    #[test]
+    fn net_client_needs_no_scene() {
+        let argv = parse("NetClient --address 127.0.0.1:6969").unwrap();
+        assert_eq!(argv.input_file, None);
+        assert_eq!(argv.address.as_deref(), Some("127.0.0.1:6969"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("").is_err());
+        assert!(parse("Paint rt/standard.rt").unwrap_err().contains("Unknown mode"));
+        assert!(parse("ToFile rt/standard.rt").unwrap_err().contains("--output"));
+        assert!(parse("ToFile rt/standard.rt -o out.jpg")
+            .unwrap_err()
+            .contains("unknown image format"));
+        assert!(parse("ToFile rt/missing.rt -o out.bmp")
+            .unwrap_err()
+            .contains("does not exist"));
+        assert!(parse("ToFile rt/standard.rt -o out.bmp --aa 2")
+            .unwrap_err()
+            .contains("--aa"));
+        assert!(parse("ToFile rt/standard.rt -o out.bmp --width")
+            .unwrap_err()
+            .contains("needs a value"));
+        assert!(parse("ToFile rt/standard.rt -o out.bmp --tone-map filmic")
+            .unwrap_err()
+            .contains("aces"));
+        assert!(parse("ToFile rt/standard.rt -o out.bmp --fast")
+            .unwrap_err()
+            .contains("Unknown option"));
+        assert!(parse("Window rt/standard.rt -o shot.png")
+            .unwrap_err()
+            .contains(".cbor"));
+        assert!(parse("Window rt/standard.rt -o scene").unwrap_err().contains(".cbor"));
+        assert!(parse("Window rt/standard.rt -o scene.cbor").is_ok());
+        assert!(parse("NetServer rt/standard.rt -o out.bmp")
+            .unwrap_err()
+            .contains("--address"));
+    }
 }
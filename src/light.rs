@@ -1,34 +1,394 @@
 use serde::{Deserialize, Serialize};
 
+use crate::color;
 use crate::num::Float0to1;
 use crate::vector::Point;
 
+/// Where the light of a `Light` comes from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum LightKind {
+    /// Shines in every direction from `origin`
+    Point { origin: Point<f32> },
+    /// Infinitely far away like the sun, all of its light travels along `direction`
+    Directional { direction: Point<f32> },
+    /// A point light limited to a cone around `direction`. Full intensity up to the inner angle from the axis,
+    /// fading out smoothly to nothing at the outer angle. The angles are stored as cosines
+    Spot {
+        origin: Point<f32>,
+        direction: Point<f32>,
+        cos_inner: f32,
+        cos_outer: f32,
+    },
+    /// A rectangle spanned by `edge1` and `edge2` around `center`, shining to both sides
+    Rect {
+        center: Point<f32>,
+        edge1: Point<f32>,
+        edge2: Point<f32>,
+    },
+    /// A glowing ball
+    Sphere { center: Point<f32>, radius: f32 },
+}
+
+/// Lights get at most this much brighter close by, like an inverse square light at a tenth of its `distance`.
+/// Without a limit a surface touching the light would be infinitely bright
+const MAX_ATTENUATION: f32 = 100.0;
+
+/// How the light gets weaker with the distance to it, directional lights never do
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    None,
+    /// Physically based, the light has its intensity at `distance` and a quarter of it at twice that
+    InverseSquare {
+        distance: f32,
+    },
+    /// `1 / (constant + linear * d + quadratic * d²)` for artistic control
+    Polynomial {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+}
+
+impl Falloff {
+    /// Lights with a position fall off like real ones unless asked not to, at full intensity 1 unit away
+    pub const DEFAULT: Self = Falloff::InverseSquare { distance: 1.0 };
+
+    /// Factor for the light at `distance`, 1 for infinitely far directional lights
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if distance.is_infinite() {
+            return 1.0;
+        }
+        match *self {
+            Falloff::None => 1.0,
+            Falloff::InverseSquare { distance: unit } => {
+                let distance = distance.max(unit / MAX_ATTENUATION.sqrt());
+                unit * unit / (distance * distance)
+            }
+            // the coefficients have no unit to take a minimum distance from, so the result is limited instead
+            Falloff::Polynomial {
+                constant,
+                linear,
+                quadratic,
+            } => 1.0 / (constant + linear * distance + quadratic * distance * distance).max(1.0 / MAX_ATTENUATION),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Light {
-    pub origin: Point<f32>,
+    pub kind: LightKind,
     intensity: f32,
     pub color: Point<u8>,
+    pub falloff: Falloff,
 }
 
 impl Light {
     pub fn new(origin: Point<f32>, intensity: Float0to1, color: Point<u8>) -> Self {
         Self {
-            origin,
+            kind: LightKind::Point { origin },
+            intensity: intensity.get(),
+            color,
+            falloff: Falloff::DEFAULT,
+        }
+    }
+    /// `direction` is the way the light travels, so it points away from the sun
+    pub fn directional(direction: Point<f32>, intensity: Float0to1, color: Point<u8>) -> Self {
+        Self {
+            kind: LightKind::Directional {
+                direction: direction.to_normalized(),
+            },
+            intensity: intensity.get(),
+            color,
+            falloff: Falloff::None,
+        }
+    }
+    /// `inner` and `outer` are the angles in degrees between the axis and the edges of the cone, `inner <= outer`
+    pub fn spot(
+        origin: Point<f32>,
+        direction: Point<f32>,
+        inner: f32,
+        outer: f32,
+        intensity: Float0to1,
+        color: Point<u8>,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                origin,
+                direction: direction.to_normalized(),
+                cos_inner: inner.to_radians().cos(),
+                cos_outer: outer.to_radians().cos(),
+            },
+            intensity: intensity.get(),
+            color,
+            falloff: Falloff::DEFAULT,
+        }
+    }
+    /// `edge1` and `edge2` are the sides of the rectangle, they should be perpendicular
+    pub fn rect(
+        center: Point<f32>,
+        edge1: Point<f32>,
+        edge2: Point<f32>,
+        intensity: Float0to1,
+        color: Point<u8>,
+    ) -> Self {
+        Self {
+            kind: LightKind::Rect { center, edge1, edge2 },
             intensity: intensity.get(),
             color,
+            falloff: Falloff::DEFAULT,
+        }
+    }
+    pub fn sphere(center: Point<f32>, diameter: f32, intensity: Float0to1, color: Point<u8>) -> Self {
+        Self {
+            kind: LightKind::Sphere {
+                center,
+                radius: diameter / 2.0,
+            },
+            intensity: intensity.get(),
+            color,
+            falloff: Falloff::DEFAULT,
+        }
+    }
+    pub fn with_falloff(self, falloff: Falloff) -> Self {
+        Self { falloff, ..self }
+    }
+    /// Linear color scaled by the intensity
+    pub fn absolute_color(&self) -> Point<f32> {
+        return color::from_u8(self.color) * self.intensity;
+    }
+    /// Normalized direction from `point` to the light and the distance to it, infinite for directional lights.
+    /// Area lights are treated as a point at their center
+    pub fn to_light(&self, point: &Point<f32>) -> (Point<f32>, f32) {
+        match self.kind {
+            LightKind::Point { origin } | LightKind::Spot { origin, .. } => direction_and_distance(*point, origin),
+            LightKind::Directional { direction } => (-direction, f32::INFINITY),
+            LightKind::Rect { center, .. } | LightKind::Sphere { center, .. } => direction_and_distance(*point, center),
+        }
+    }
+    /// Like `to_light` for `count` points spread over an area light, so shadows get a soft edge.
+    /// The area is split into a grid of `count` rounded down to a square cells with one random point in each,
+    /// other lights have a single sample
+    pub fn samples(&self, point: &Point<f32>, count: usize) -> impl ExactSizeIterator<Item = (Point<f32>, f32)> + '_ {
+        let side = match self.kind {
+            LightKind::Rect { .. } | LightKind::Sphere { .. } => ((count as f32).sqrt() as usize).max(1),
+            _ => 1,
+        };
+        let point = *point;
+        let mut random = Jitter::new(&point);
+        (0..side * side).map(move |i| {
+            let x = ((i % side) as f32 + random.next()) / side as f32;
+            let y = ((i / side) as f32 + random.next()) / side as f32;
+            let sample = match self.kind {
+                LightKind::Rect { center, edge1, edge2 } => center + edge1 * (x - 0.5) + edge2 * (y - 0.5),
+                LightKind::Sphere { center, radius } => {
+                    // the disk of the sphere facing the point, uniform in area
+                    let (axis, _) = direction_and_distance(point, center);
+                    let (u, v) = perpendicular(axis);
+                    let (r, angle) = (radius * x.sqrt(), y * std::f32::consts::TAU);
+                    center + u * (r * angle.cos()) + v * (r * angle.sin())
+                }
+                _ => return self.to_light(&point),
+            };
+            direction_and_distance(point, sample)
+        })
+    }
+    /// How much of the light shines in the direction opposite to `to_light`, 1 outside spot lights
+    fn cone(&self, to_light: &Point<f32>) -> f32 {
+        match self.kind {
+            LightKind::Spot {
+                direction,
+                cos_inner,
+                cos_outer,
+                ..
+            } => smoothstep(cos_outer, cos_inner, -to_light.dot(&direction)),
+            _ => 1.0,
+        }
+    }
+    /// Lambert term for light arriving from `to_light`
+    pub fn relative_intensity(&self, to_light: &Point<f32>, normal: &Point<f32>) -> f32 {
+        let intensity = self.intensity * self.cone(to_light) * normal.dot(to_light).max(0.0);
+        return intensity.min(1.0);
+    }
+    /// Blinn-Phong highlight seen from `to_viewer`, 0 when the light is behind the surface
+    pub fn specular_intensity(
+        &self,
+        to_light: &Point<f32>,
+        normal: &Point<f32>,
+        to_viewer: &Point<f32>,
+        shininess: f32,
+    ) -> f32 {
+        if normal.dot(to_light) <= 0.0 {
+            return 0.0;
         }
+        let halfway = (*to_light + *to_viewer).to_normalized();
+        return self.intensity * self.cone(to_light) * normal.dot(&halfway).max(0.0).powf(shininess);
+    }
+}
+
+fn direction_and_distance(from: Point<f32>, to: Point<f32>) -> (Point<f32>, f32) {
+    let v = to - from;
+    let distance = v.length();
+    // a point inside a light, like at the center of a sphere light, would normalize a zero vector to NaN
+    if distance <= 0.0 {
+        return (Point::new(0.0, 1.0, 0.0), 0.0);
+    }
+    (v / distance, distance)
+}
+
+/// Two unit vectors perpendicular to `axis` and each other
+fn perpendicular(axis: Point<f32>) -> (Point<f32>, Point<f32>) {
+    let other = match axis.x.abs() < 0.9 {
+        true => Point::new(1.0, 0.0, 0.0),
+        false => Point::new(0.0, 1.0, 0.0),
+    };
+    let u = axis.cross(&other).to_normalized();
+    (u, axis.cross(&u))
+}
+
+/// Numbers in 0..1 that only depend on the shaded point, so a pixel looks the same on every render and client
+struct Jitter(u32);
+
+impl Jitter {
+    fn new(point: &Point<f32>) -> Self {
+        // xorshift gets stuck at 0
+        Self((point.x.to_bits() ^ point.y.to_bits().rotate_left(11) ^ point.z.to_bits().rotate_left(22)) | 1)
+    }
+
+    /// xorshift32 followed by a multiplicative hash, as xorshift alone is poor for similar seeds
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        let hash = self.0.wrapping_mul(0x9E37_79B9) >> 8;
+        hash as f32 / (1 << 24) as f32
+    }
+}
+
+/// 0 below `edge0`, 1 above `edge1` and a smooth curve in between
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1.0 } else { 0.0 }; // hard edge when inner == outer
     }
-    pub fn absolute_color(&self) -> Point<u8> {
-        return Point::new(
-            (self.color.x as f32 * self.intensity) as u8,
-            (self.color.y as f32 * self.intensity) as u8,
-            (self.color.z as f32 * self.intensity) as u8,
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Falloff, Light};
+    use crate::num::Float0to1;
+    use crate::vector::Point;
+
+    #[test]
+    fn spot_cone() {
+        let (full, white) = (Float0to1::new(1.0).unwrap(), Point::homogeneous(255));
+        let down = Point::new(0.0, -1.0, 0.0);
+        let up = Point::new(0.0, 1.0, 0.0);
+        let spot = Light::spot(Point::new(0.0, 1.0, 0.0), down, 20.0, 40.0, full, white);
+        let lit = |light: &Light, point: Point<f32>| light.relative_intensity(&light.to_light(&point).0, &up);
+        let at = |degrees: f32| lit(&spot, Point::new(degrees.to_radians().tan(), 0.0, 0.0));
+
+        assert_eq!(at(0.0), 1.0);
+        assert!(at(10.0) > 0.9); // only the cosine term
+
+        // fades out between the inner and outer angle
+        let halfway = at(30.0) / 30.0f32.to_radians().cos();
+        assert!(halfway > 0.2 && halfway < 0.8, "{halfway}");
+        assert_eq!(at(45.0), 0.0);
+
+        let hard = Light::spot(Point::homogeneous(0.0), down, 30.0, 30.0, full, white);
+        assert!(lit(&hard, Point::new(0.5, -1.0, 0.0)) > 0.0);
+        assert_eq!(lit(&hard, Point::new(0.6, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let (half, white) = (Float0to1::new(0.5).unwrap(), Point::homogeneous(255));
+        let down = Point::new(0.0, -1.0, 0.0);
+        let lights = vec![
+            Light::new(Point::new(1.0, 2.0, 3.0), half, white),
+            Light::directional(down, half, white),
+            Light::spot(Point::new(0.0, 2.0, 0.0), down, 10.0, 20.0, half, white),
+        ];
+        let bytes = serde_cbor::to_vec(&lights).unwrap();
+        let read: Vec<Light> = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", lights));
+    }
+
+    #[test]
+    fn area_samples_are_stratified() {
+        let (full, white) = (Float0to1::new(1.0).unwrap(), Point::homogeneous(255));
+        let rect = Light::rect(
+            Point::new(0.0, 4.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, 2.0),
+            full,
+            white,
+        );
+        let point = Point::new(0.3, 0.0, -0.2);
+
+        let samples: Vec<_> = rect.samples(&point, 18).collect();
+        assert_eq!(samples.len(), 16);
+        let mut cells = vec![];
+        for (to_light, distance) in samples {
+            let sample = point + to_light * distance;
+            assert!((sample.y - 4.0).abs() < 1e-4);
+            // cells of 0.5 by 0.5
+            cells.push((((sample.x + 1.0) * 2.0) as usize, ((sample.z + 1.0) * 2.0) as usize));
+        }
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 16);
+        assert!(cells.iter().all(|&(x, z)| x < 4 && z < 4));
+        // the same point gets the same samples
+        assert_eq!(
+            format!("{:?}", rect.samples(&point, 16).collect::<Vec<_>>()),
+            format!("{:?}", rect.samples(&point, 16).collect::<Vec<_>>())
+        );
+
+        let sphere = Light::sphere(Point::new(0.0, 4.0, 0.0), 1.0, full, white);
+        for (to_light, distance) in sphere.samples(&point, 9) {
+            let sample = point + to_light * distance;
+            assert!(sample.distance2(&Point::new(0.0, 4.0, 0.0)) <= 0.25 + 1e-4);
+        }
+        // every sample from the center of the sphere is a direction
+        for (to_light, distance) in sphere.samples(&Point::new(0.0, 4.0, 0.0), 16) {
+            assert!((to_light.length() - 1.0).abs() < 1e-4, "{:?}", to_light);
+            assert!(distance <= 0.5 + 1e-4);
+        }
+        assert_eq!(
+            Light::new(point, full, white)
+                .samples(&Point::homogeneous(0.0), 16)
+                .len(),
+            1
         );
     }
-    pub fn relative_intensity(&self, point: &Point<f32>, normal: &Point<f32>) -> f32 {
-        let to_light = (self.origin - *point).to_normalized();
 
-        let intensity = self.intensity * normal.dot(&to_light).max(0.0);
-        return intensity.min(1.0);
+    #[test]
+    fn falloff() {
+        let inverse_square = Falloff::InverseSquare { distance: 2.0 };
+        assert_eq!(inverse_square.attenuation(2.0), 1.0);
+        assert_eq!(inverse_square.attenuation(4.0), 0.25);
+        assert_eq!(inverse_square.attenuation(1.0), 4.0);
+        assert_eq!(inverse_square.attenuation(f32::INFINITY), 1.0);
+        // no closer than a tenth of the distance
+        assert_eq!(inverse_square.attenuation(0.2), inverse_square.attenuation(0.0));
+        assert!((inverse_square.attenuation(0.0) - 100.0).abs() < 1e-3);
+
+        let polynomial = Falloff::Polynomial {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        assert_eq!(polynomial.attenuation(0.0), 1.0);
+        assert_eq!(polynomial.attenuation(2.0), 1.0 / 3.0);
+        let linear = Falloff::Polynomial {
+            constant: 0.0,
+            linear: 1.0,
+            quadratic: 0.0,
+        };
+        assert_eq!(linear.attenuation(0.5), 2.0);
+        assert!((linear.attenuation(0.0) - 100.0).abs() < 1e-3);
+        assert_eq!(Falloff::None.attenuation(1000.0), 1.0);
     }
 }
@@ -3,29 +3,41 @@
 extern crate bmp;
 extern crate num_integer;
 
-use crate::frame_buffer::PixelProvider;
-use crate::init::Mode;
+use crate::frame_buffer::{CameraFrameBuffers, PixelProvider};
+use crate::init::{CameraSelection, Mode};
 use crate::net::NetClient;
 use crate::net::NetServer;
 use crate::renderer::render_multithreaded;
-use crate::util::ExitOnError;
+use crate::util::{set_threads, ExitOnError};
 use init::get_resolution;
 use init::get_scene;
 use init::Argv;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 mod camera;
+mod color;
+mod cylinder;
 mod frame_buffer;
 mod helpers;
+mod image_format;
 mod init;
 mod light;
+mod material;
 mod net;
 mod num;
 mod octree;
+mod plane;
+mod primitive;
 mod progress_logger;
 mod random_iterator;
 mod renderer;
 mod resolution;
 mod scene_readers;
+mod sphere;
+mod square;
+mod texture;
+mod tone_map;
 mod triangle;
 mod util;
 mod vector;
@@ -33,32 +45,58 @@ mod window;
 
 fn main() {
     let argv = Argv::new();
+    if let Some(threads) = argv.threads {
+        set_threads(threads);
+    }
 
     match argv.mode {
         Mode::NetServer => {
-            let resolution = get_resolution();
-            let scene = get_scene(&argv.input_file).unwrap();
-            let mut server = NetServer::new(&argv.address.unwrap(), scene, &resolution);
+            let mut scene = get_scene(&argv).exit_with_error();
+            let resolution = get_resolution(&scene, &argv);
+            scene.resolution = Some(resolution); // clients render at the resolution of the server
+            let cameras = argv.camera.indices(&scene).exit_with_error();
+            let output_file = argv.output_file.unwrap();
+            let address = argv.address.unwrap();
+            let mut server = NetServer::new(
+                &address,
+                scene,
+                &resolution,
+                cameras,
+                argv.seed,
+                output_file,
+                argv.display,
+            );
             server.start()
         }
         Mode::NetClient => net_client(&argv),
         Mode::ToFile => {
-            let resolution = get_resolution();
-            let scene = get_scene(&argv.input_file).unwrap();
-            let mut fb = frame_buffer::FrameBuffer::new(&resolution).unwrap();
-            let pixel_provider = PixelProvider::new(&resolution);
-            // let scene = Arc::new(RwLock::new(scene));
-            let mut pixels = render_multithreaded(&scene, &resolution, pixel_provider);
-            fb.set_pixel_from_iterator(&mut pixels);
-            fb.save_as_bmp(&argv.output_file.unwrap()).unwrap();
+            let scene = Arc::new(get_scene(&argv).exit_with_error());
+            let resolution = get_resolution(&scene, &argv);
+            let cameras = argv.camera.indices(&scene).exit_with_error();
+            let mut frame_buffers = CameraFrameBuffers::new(&resolution, &cameras);
+            let pixel_provider = PixelProvider::new(&resolution, cameras, argv.seed);
+            let pixels = render_multithreaded(scene.clone(), &scene.cameras, &resolution, pixel_provider);
+            frame_buffers.set_pixel_from_iterator(&mut pixels.iter());
+            frame_buffers
+                .save(&argv.output_file.unwrap(), &argv.display)
+                .exit_with_error();
+        }
+        Mode::Window => {
+            let scene = get_scene(&argv).exit_with_error();
+            let resolution = get_resolution(&scene, &argv);
+            let camera = match argv.camera {
+                CameraSelection::Index(_) => argv.camera.indices(&scene).exit_with_error()[0],
+                CameraSelection::All => 0, // C cycles through the cameras
+            };
+            let save_path = argv.output_file.unwrap_or(PathBuf::from("scene.cbor"));
+            window::loop_until_closed(Arc::new(scene), resolution, camera, argv.seed, argv.display, save_path)
+                .exit_with_error();
         }
-        Mode::Window => {}
     }
 }
 
 fn net_client(argv: &Argv) {
     let mut pixel_provider = NetClient::new(&argv.address.as_ref().unwrap()).exit_with("Failed to connect to server");
-    let resolution = get_resolution();
     let mut rendered_pixel_blocks = 0;
 
     loop {
@@ -71,8 +109,9 @@ fn net_client(argv: &Argv) {
         // let pixel_requests = split(&pixel_requests, threads());
         let pixel_requests = Some(pixel_requests).into_iter();
 
-        let scene = &pixel_provider.scene.clone().unwrap();
-        let pixel_bufs = render_multithreaded(scene, &resolution, pixel_requests);
+        let scene = pixel_provider.scene.clone().unwrap();
+        let resolution = scene.resolution.unwrap_or_default();
+        let pixel_bufs = render_multithreaded(scene.clone(), &scene.cameras, &resolution, pixel_requests);
 
         for pixel_buf in pixel_bufs {
             pixel_provider.send_pixel(pixel_buf);
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::vector::Point;
+
+/// Bump scale of materials without `-bm`, which is a multiplier of this in MTL files
+pub const BASE_BUMP_SCALE: f32 = 4.0;
+
+/// How a surface reacts to light, shapes refer to one by their index into `Scene.materials`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Material {
+    /// Linear, like every color in the renderer
+    pub diffuse: Point<f32>,
+    /// Color of the Blinn-Phong highlights, black for matte surfaces
+    pub specular: Point<f32>,
+    /// Exponent of the highlights, higher gives smaller and sharper highlights
+    pub shininess: f32,
+    /// 0..1, how much of a mirror the surface is
+    pub reflectivity: f32,
+    /// 0..1, how much light passes through the surface
+    pub transparency: f32,
+    /// Index of refraction, 1.0 for air
+    pub ior: f32,
+    /// Light given off by the surface itself, added regardless of the lights
+    pub emission: Point<f32>,
+    /// Index into `Scene.textures`, replaces `diffuse` on shapes with texture coordinates
+    pub texture: Option<usize>,
+    /// Index into `Scene.textures` of a tangent-space normal map
+    pub normal_map: Option<usize>,
+    /// Index into `Scene.textures` of a grayscale height map
+    pub bump_map: Option<usize>,
+    /// How steep a full black to white step between two texels of `bump_map` tilts the normal
+    pub bump_scale: f32,
+}
+
+impl Material {
+    pub fn matte(color: Point<u8>) -> Self {
+        Self {
+            diffuse: color::from_u8(color),
+            specular: Point::homogeneous(0.0),
+            shininess: 32.0,
+            reflectivity: 0.0,
+            transparency: 0.0,
+            ior: 1.0,
+            emission: Point::homogeneous(0.0),
+            texture: None,
+            normal_map: None,
+            bump_map: None,
+            bump_scale: BASE_BUMP_SCALE,
+        }
+    }
+
+    /// Any of the textures needs texture coordinates
+    pub fn is_textured(&self) -> bool {
+        self.texture.is_some() || self.normal_map.is_some() || self.bump_map.is_some()
+    }
+
+    /// Bit patterns of all fields, equal materials have equal keys
+    fn key(&self) -> [u32; 17] {
+        [
+            self.diffuse.x.to_bits(),
+            self.diffuse.y.to_bits(),
+            self.diffuse.z.to_bits(),
+            self.specular.x.to_bits(),
+            self.specular.y.to_bits(),
+            self.specular.z.to_bits(),
+            self.shininess.to_bits(),
+            self.reflectivity.to_bits(),
+            self.transparency.to_bits(),
+            self.ior.to_bits(),
+            self.emission.x.to_bits(),
+            self.emission.y.to_bits(),
+            self.emission.z.to_bits(),
+            self.texture.map_or(u32::MAX, |texture| texture as u32),
+            self.normal_map.map_or(u32::MAX, |texture| texture as u32),
+            self.bump_map.map_or(u32::MAX, |texture| texture as u32),
+            self.bump_scale.to_bits(),
+        ]
+    }
+}
+
+/// Collects the materials of a scene while it is read, equal materials share an index
+#[derive(Default)]
+pub struct MaterialStore {
+    materials: Vec<Material>,
+    indices: HashMap<[u32; 17], usize>,
+}
+
+impl MaterialStore {
+    /// Returns the index to store in the shape
+    pub fn add(&mut self, material: Material) -> usize {
+        let materials = &mut self.materials;
+        *self.indices.entry(material.key()).or_insert_with(|| {
+            materials.push(material);
+            materials.len() - 1
+        })
+    }
+
+    pub fn into_vec(self) -> Vec<Material> {
+        self.materials
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Material, MaterialStore};
+    use crate::vector::Point;
+
+    #[test]
+    fn equal_materials_are_stored_once() {
+        let mut store = MaterialStore::default();
+        let red = store.add(Material::matte(Point::new(255, 0, 0)));
+        let blue = store.add(Material::matte(Point::new(0, 0, 255)));
+        assert_eq!(store.add(Material::matte(Point::new(255, 0, 0))), red);
+        assert_ne!(red, blue);
+
+        let mut shiny = Material::matte(Point::new(255, 0, 0));
+        shiny.specular = Point::homogeneous(1.0);
+        assert_eq!(store.add(shiny), 2);
+        assert_eq!(store.into_vec().len(), 3);
+    }
+}
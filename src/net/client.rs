@@ -2,12 +2,13 @@ use super::socket::NetSocket;
 use super::NetResponse;
 use crate::net::NetCommand;
 use crate::scene_readers::Scene;
-use crate::util::{PixelReqBuffer, PixelResBuffer};
+use crate::util::{PixelReqBuffer, PixelResBuffer, PIXEL_BUFFER_SIZE};
 use std::io::Error;
 use std::net::TcpStream;
+use std::sync::Arc;
 
 pub struct NetClient {
-    pub scene: Option<Scene>,
+    pub scene: Option<Arc<Scene>>,
     reader: NetSocket,
 }
 
@@ -21,14 +22,21 @@ impl NetClient {
         })
     }
 
+    /// Returns an empty buffer when there is nothing left to render
     pub fn read_next_pixel(&mut self) -> PixelReqBuffer {
-        let message: Vec<u8> = self.reader.read().unwrap();
+        let message: Vec<u8> = match self.reader.read() {
+            Ok(message) => message,
+            Err(e) => {
+                println!("Server closed the connection: {}", e);
+                return [None; PIXEL_BUFFER_SIZE];
+            }
+        };
         let cmd = serde_cbor::from_slice(&message).unwrap();
 
         match cmd {
             NetCommand::ReadScene(scene) => {
                 println!("Received scene");
-                self.scene = Some(scene)
+                self.scene = Some(Arc::new(scene))
             }
             NetCommand::RenderPixBuf(pixel_req) => {
                 if self.scene.is_none() {
@@ -1,10 +1,11 @@
-use crate::frame_buffer::{FrameBuffer, PixelProvider};
+use crate::frame_buffer::{CameraFrameBuffers, PixelProvider};
 use crate::net::{NetCommand, NetResponse, NetSocket};
 use crate::resolution::Resolution;
 use crate::scene_readers::Scene;
+use crate::tone_map::DisplayTransform;
 use std::io::ErrorKind;
 use std::net::TcpListener;
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -19,18 +20,31 @@ pub struct NetServer {
     address: String,
     connections: Arc<Mutex<Vec<(SocketState, NetSocket)>>>,
     scene: Scene,
-    frame_buffer: FrameBuffer,
+    frame_buffers: CameraFrameBuffers,
     pixel_stream: PixelProvider,
+    output_file: PathBuf,
+    display: DisplayTransform,
 }
 
 impl NetServer {
-    pub fn new(address: &str, scene: Scene, resolution: &Resolution) -> NetServer {
+    /// Distributes the pixels of all `cameras` over the clients and saves them to `output_file` when done
+    pub fn new(
+        address: &str,
+        scene: Scene,
+        resolution: &Resolution,
+        cameras: Vec<usize>,
+        seed: u64,
+        output_file: PathBuf,
+        display: DisplayTransform,
+    ) -> NetServer {
         NetServer {
             address: address.to_string(),
             connections: Arc::new(Mutex::new(Vec::new())),
             scene,
-            frame_buffer: FrameBuffer::new(resolution).unwrap(),
-            pixel_stream: PixelProvider::new(resolution),
+            frame_buffers: CameraFrameBuffers::new(resolution, &cameras),
+            pixel_stream: PixelProvider::new(resolution, cameras, seed),
+            output_file,
+            display,
         }
     }
 
@@ -62,8 +76,12 @@ impl NetServer {
                 .filter(|(state, _)| *state != SocketState::Disconnected)
             {
                 #[rustfmt::skip]
-                handle_socket( state, socket, &mut self.frame_buffer, &mut self.pixel_stream, &self.scene);
-                if self.frame_buffer.is_complete() {
+                handle_socket( state, socket, &mut self.frame_buffers, &mut self.pixel_stream, &self.scene);
+                if self.frame_buffers.is_complete() {
+                    println!("All pixels rendered");
+                    if let Err(e) = self.frame_buffers.save(&self.output_file, &self.display) {
+                        eprintln!("Failed to save the image: {}", e);
+                    }
                     return;
                 }
             }
@@ -72,50 +90,49 @@ impl NetServer {
     }
 }
 
+/// Every client has at most one pixel buffer to render, the next one is sent when its result is received
 fn handle_socket(
     state: &mut SocketState,
     socket: &mut NetSocket,
-    frame_buffer: &mut FrameBuffer,
+    frame_buffers: &mut CameraFrameBuffers,
     pixel_stream: &mut PixelProvider,
     scene: &Scene,
 ) {
-    if *state == SocketState::Initiated {
+    if *state == SocketState::Uninitialized {
+        send(state, socket, &NetCommand::ReadScene(scene.clone()));
+        if *state == SocketState::Disconnected {
+            return;
+        }
+        *state = SocketState::Initiated;
+    } else {
         let response = socket.read();
         match response {
             Ok(response) => {
                 let response: NetResponse = serde_cbor::from_slice(&response).unwrap();
 
                 match response {
-                    NetResponse::RenderPixBuf(buffer) => frame_buffer.set_pixel_from_buffer(&buffer),
+                    NetResponse::RenderPixBuf(buffer) => frame_buffers.set_pixel_from_buffer(&buffer),
                 }
-                println!("Progress: {}%", frame_buffer.progress().get() * 100.0);
+                println!("Progress: {}%", frame_buffers.progress().get() * 100.0);
             }
             Err(e) => {
                 if e.kind() != ErrorKind::WouldBlock {
                     eprintln!("Client error: {}", e);
                     *state = SocketState::Disconnected;
-                    return;
                 }
+                return; // the client is still rendering
             }
         }
     }
 
-    let cmd = match state {
-        SocketState::Uninitialized => NetCommand::ReadScene(scene.clone()),
-        SocketState::Initiated => {
-            let pix_buf = pixel_stream.get_coordinates();
-            if frame_buffer.is_complete() {
-                println!("All pixels rendered, resetting");
-                frame_buffer.save_as_bmp(Path::new("output.bmp")).unwrap();
-                pixel_stream.reset();
-                panic!("All pixels rendered");
-                return;
-            }
-            NetCommand::RenderPixBuf(pix_buf)
-        }
-        _ => unreachable!(),
-    };
-    let binding = serde_cbor::to_vec(&cmd).unwrap();
+    if frame_buffers.is_complete() {
+        return; // NetServer::start saves the result
+    }
+    send(state, socket, &NetCommand::RenderPixBuf(pixel_stream.get_coordinates()));
+}
+
+fn send(state: &mut SocketState, socket: &mut NetSocket, cmd: &NetCommand) {
+    let binding = serde_cbor::to_vec(cmd).unwrap();
 
     // let mut cmd = format!("{:?}", cmd);
     // cmd.truncate(50);
@@ -126,8 +143,4 @@ fn handle_socket(
             *state = SocketState::Disconnected;
         }
     }
-
-    if *state == SocketState::Uninitialized {
-        *state = SocketState::Initiated;
-    }
 }
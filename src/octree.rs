@@ -65,6 +65,10 @@ impl AABB {
             && point.z <= self.max.z;
     }
 
+    pub fn is_finite(&self) -> bool {
+        return self.min.is_finite() && self.max.is_finite();
+    }
+
     pub fn is_inside_all(&self, points: &[Point<f32>]) -> bool {
         points.iter().all(|point| self.is_inside(point))
     }
@@ -139,7 +143,7 @@ const MAX_SHAPES_PER_OCTREE: usize = 10;
 ///    Point::new(0.0, 0.0, 0.0),
 ///    Point::new(1.0, 0.0, 0.0),
 ///    Point::new(0.0, 1.0, 0.0),
-///    Point::new(255, 0, 0),
+///    0,
 /// )];
 /// let octree = Octree::new(shapes);
 /// assert_eq!(octree.shapes_count(), 1);
@@ -149,6 +153,8 @@ pub struct Octree<T> {
     aabb: AABB,
     children: Vec<Octree<T>>,
     shapes: Vec<T>,
+    /// Shapes without a finite AABB, like planes, only live in the root and are checked for every ray
+    unbounded: Vec<T>,
 }
 
 impl<T> Octree<T>
@@ -156,10 +162,12 @@ where
     T: Shape + Intersect,
 {
     pub fn new(shapes: Vec<T>) -> Self {
+        let (shapes, unbounded): (Vec<T>, Vec<T>) = shapes.into_iter().partition(|shape| shape.aabb().is_finite());
         let mut this = Self {
             aabb: AABB::new(Point::homogeneous(f32::MIN), Point::homogeneous(f32::MAX)),
             children: Vec::new(),
             shapes,
+            unbounded,
         };
         if this.shapes.len() != 0 {
             this.shrink_to_fit();
@@ -173,10 +181,13 @@ where
     }
 
     pub fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        for shape in &self.unbounded {
+            closest = Hit::closest(closest, shape.hit(ray));
+        }
         if !self.aabb.hit(ray) {
-            return None;
+            return closest;
         }
-        let mut closest: Option<Hit> = None;
 
         for shape in &self.shapes {
             let hit = shape.hit(ray);
@@ -209,9 +220,14 @@ where
         return closest;
     }
 
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &T> + '_> {
+        let own = self.unbounded.iter().chain(self.shapes.iter());
+        Box::new(own.chain(self.children.iter().flat_map(|child| child.iter())))
+    }
+
     #[allow(dead_code)]
     pub fn shapes_count(&self) -> usize {
-        let mut count = self.shapes.len();
+        let mut count = self.shapes.len() + self.unbounded.len();
         for child in &self.children {
             count += child.shapes_count();
         }
@@ -260,6 +276,7 @@ where
             aabb,
             children: Vec::new(),
             shapes: Vec::new(),
+            unbounded: Vec::new(),
         }
     }
 
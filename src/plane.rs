@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use crate::octree::AABB;
+use crate::util::{correct_normal, Hit, Intersect, Ray, Shape, RAY_EPSILON};
+use crate::vector::Point;
+
+/// Infinite plane, it has no finite bounding box so the octree keeps it out of its children
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Plane {
+    pub point: Point<f32>,
+    pub normal: Point<f32>,
+    /// Index into `Scene.materials`
+    pub material: usize,
+}
+
+impl Plane {
+    pub fn new(point: Point<f32>, normal: Point<f32>, material: usize) -> Self {
+        Self {
+            point,
+            normal: normal.to_normalized(),
+            material,
+        }
+    }
+}
+
+impl Intersect for Plane {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let denominator = self.normal.dot(&ray.dir);
+        if denominator.abs() < f32::EPSILON {
+            return None; // Ray is parallel to the plane
+        }
+        let t = (self.point - ray.origin).dot(&self.normal) / denominator;
+        if t < RAY_EPSILON {
+            return None;
+        }
+        let (normal, front_face) = correct_normal(self.normal, &ray.dir);
+        Some(Hit::new(
+            t,
+            ray.origin,
+            ray.origin + ray.dir * t,
+            normal,
+            front_face,
+            self.material,
+        ))
+    }
+}
+
+impl Shape for Plane {
+    fn is_inside_aabb(&self, _aabb: &AABB) -> bool {
+        false
+    }
+
+    fn aabb(&self) -> AABB {
+        AABB::new(Point::homogeneous(-f32::INFINITY), Point::homogeneous(f32::INFINITY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::octree::Octree;
+    use crate::plane::Plane;
+    use crate::util::{Intersect, Ray};
+    use crate::vector::Point;
+
+    fn floor() -> Plane {
+        Plane::new(Point::new(0.0, -1.0, 0.0), Point::new(0.0, 1.0, 0.0), 0)
+    }
+
+    #[test]
+    fn hit() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, -1.0, 0.0));
+        let hit = floor().hit(&ray).unwrap();
+        assert!((hit.dist - 1.0).abs() < 1e-5);
+        assert!(hit.normal.approx_eq(&Point::new(0.0, 1.0, 0.0), 1e-5));
+
+        let from_below = Ray::new(Point::new(0.0, -2.0, 0.0), Point::new(0.0, 1.0, 0.0));
+        let hit = floor().hit(&from_below).unwrap();
+        assert!(hit.normal.approx_eq(&Point::new(0.0, -1.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn miss() {
+        let parallel = Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0));
+        assert!(floor().hit(&parallel).is_none());
+
+        let away = Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+        assert!(floor().hit(&away).is_none());
+    }
+
+    #[test]
+    fn octree_keeps_unbounded_shapes() {
+        let octree = Octree::new(vec![floor()]);
+        assert_eq!(octree.shapes_count(), 1);
+
+        let ray = Ray::new(Point::new(1000.0, 0.0, -1000.0), Point::new(0.0, -1.0, 0.0));
+        assert!(octree.hit(&ray).is_some());
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cylinder::Cylinder;
+use crate::octree::AABB;
+use crate::plane::Plane;
+use crate::sphere::Sphere;
+use crate::square::Square;
+use crate::triangle::Triangle;
+use crate::util::{Hit, Intersect, Ray, Shape};
+
+/// Every kind of geometry a scene can contain, so they can all live in one `Octree`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Primitive {
+    Triangle(Triangle),
+    Sphere(Sphere),
+    Cylinder(Cylinder),
+    Square(Square),
+    Plane(Plane),
+}
+
+impl Intersect for Primitive {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        match self {
+            Primitive::Triangle(triangle) => triangle.hit(ray),
+            Primitive::Sphere(sphere) => sphere.hit(ray),
+            Primitive::Cylinder(cylinder) => cylinder.hit(ray),
+            Primitive::Square(square) => square.hit(ray),
+            Primitive::Plane(plane) => plane.hit(ray),
+        }
+    }
+}
+
+impl Shape for Primitive {
+    fn is_inside_aabb(&self, aabb: &AABB) -> bool {
+        match self {
+            Primitive::Triangle(triangle) => triangle.is_inside_aabb(aabb),
+            Primitive::Sphere(sphere) => sphere.is_inside_aabb(aabb),
+            Primitive::Cylinder(cylinder) => cylinder.is_inside_aabb(aabb),
+            Primitive::Square(square) => square.is_inside_aabb(aabb),
+            Primitive::Plane(plane) => plane.is_inside_aabb(aabb),
+        }
+    }
+
+    fn aabb(&self) -> AABB {
+        match self {
+            Primitive::Triangle(triangle) => triangle.aabb(),
+            Primitive::Sphere(sphere) => sphere.aabb(),
+            Primitive::Cylinder(cylinder) => cylinder.aabb(),
+            Primitive::Square(square) => square.aabb(),
+            Primitive::Plane(plane) => plane.aabb(),
+        }
+    }
+}
@@ -5,6 +5,7 @@
 #[derive(PartialEq, Debug, Clone)]
 pub struct RandomIterator {
     max: usize,
+    seed: usize,
     offset: usize,
     step_size: usize,
     actual: usize,
@@ -13,18 +14,25 @@ pub struct RandomIterator {
 
 impl RandomIterator {
     pub fn new(max: usize) -> Self {
+        Self::with_seed(max, 0)
+    }
+    /// Different seeds start at a different offset, so the order changes
+    pub fn with_seed(max: usize, seed: u64) -> Self {
+        let step_size = (max / 100) + 1;
+        let seed = (seed % step_size as u64) as usize;
         Self {
             max,
-            offset: 0,
-            actual: 0,
-            step_size: (max / 100) + 1,
+            seed,
+            offset: seed,
+            actual: seed,
+            step_size,
             i: 0,
         }
     }
     pub fn reset(&mut self) {
         self.i = 0;
-        self.offset = 0;
-        self.actual = 0;
+        self.offset = self.seed;
+        self.actual = self.seed;
     }
     pub fn i(&self) -> usize {
         self.i
@@ -42,7 +50,7 @@ impl Iterator for RandomIterator {
         let out = self.actual;
         self.actual += self.step_size;
         if self.actual >= self.max {
-            self.offset += 1;
+            self.offset = (self.offset + 1) % self.step_size;
             self.actual = self.offset;
         }
         Some(out)
@@ -54,8 +62,12 @@ mod random_iterator_test {
     use crate::random_iterator::RandomIterator;
 
     fn test(max: usize) {
+        test_seeded(max, 0);
+    }
+
+    fn test_seeded(max: usize, seed: u64) {
         let mut nums = vec![];
-        let mut generator = RandomIterator::new(max);
+        let mut generator = RandomIterator::with_seed(max, seed);
         for _ in 0..max {
             nums.push(generator.next().unwrap());
         }
@@ -81,4 +93,16 @@ mod random_iterator_test {
         test(1002);
         test(10000);
     }
+
+    #[test]
+    fn seeded() {
+        test_seeded(1, 3);
+        test_seeded(1001, 5);
+        test_seeded(10000, 42);
+        test_seeded(10000, 1234567);
+        assert_ne!(
+            RandomIterator::with_seed(10000, 1).next(),
+            RandomIterator::with_seed(10000, 2).next()
+        );
+    }
 }
@@ -1,10 +1,35 @@
 use crate::camera::Camera;
-use crate::light::Light;
+use crate::color;
 use crate::resolution::Resolution;
 use crate::scene_readers::Scene;
-use crate::util::{threads, Hit, PixelReqBuffer, PixelRes, PixelResBuffer, Ray, PIXEL_BUFFER_SIZE};
+use crate::util::{
+    offset_from_surface, reflect, refract, schlick, threads, Hit, PixelReqBuffer, PixelRes, PixelResBuffer, Ray,
+    PIXEL_BUFFER_SIZE,
+};
 use crate::vector::Point;
-use std::sync::{mpsc, Arc, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Shadow rays passing through more transparent surfaces than this count as blocked
+const MAX_SHADOW_OCCLUDERS: usize = 16;
+
+/// Options that change the rendered image. They are part of the scene so network clients render with them too
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Reflected and refracted rays stop after this many bounces, 0 disables them
+    pub max_depth: usize,
+    /// Shadow rays per area light and shading point, rounded down to a square
+    pub light_samples: usize,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            light_samples: 16,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Renderer {
@@ -35,57 +60,160 @@ impl Renderer {
     }
 
     pub fn hit(&self, scene: &Scene, ray: &Ray) -> Option<Hit> {
-        return scene.triangles.hit(&ray);
+        return scene.shapes.hit(&ray);
     }
 
     // TODO: move to Hit
-    pub fn get_color(&self, scene: &Scene, hit: &Hit) -> Point<u8> {
-        let color = Point::new(hit.color.x as f32, hit.color.y as f32, hit.color.z as f32);
-        let mut acc = Point::homogeneous(0.0);
-        let mut additions: usize = 0;
+    /// The sum of the ambient light, Lambert diffuse plus Blinn-Phong specular of every light and the emission.
+    /// Lights add up like real ones, the display transform brings the result into range
+    pub fn get_color(&self, scene: &Scene, hit: &Hit) -> Point<f32> {
+        let material = &scene.materials[hit.material];
+        let diffuse_color = match (material.texture, hit.uv) {
+            (Some(texture), Some(uv)) => scene.textures[texture].sample(uv),
+            _ => material.diffuse,
+        };
+        let normal = self.shading_normal(scene, hit);
+        let to_viewer = (hit.origin - hit.point).to_normalized();
+        let mut acc = diffuse_color * scene.ambient.absolute_color();
 
         for light in &scene.lights {
-            if !self.is_clear_path(scene, &hit.point, &light) {
-                continue;
-            }
+            let light_color = color::from_u8(light.color);
+            // area lights are the average of their samples
+            let samples = light.samples(&hit.point, scene.settings.light_samples);
+            let weight = 1.0 / samples.len() as f32;
+            for (to_light, distance) in samples {
+                let transmission = self.light_transmission(scene, hit, to_light, distance);
+                if transmission <= 0.0 {
+                    continue;
+                }
 
-            let relative = color * light.relative_intensity(&hit.point, &hit.normal);
-            acc += relative;
-            additions += 1;
+                let diffuse = diffuse_color * light.relative_intensity(&to_light, &normal);
+                let specular =
+                    material.specular * light.specular_intensity(&to_light, &normal, &to_viewer, material.shininess);
+                let attenuation = light.falloff.attenuation(distance);
+                acc += (diffuse + specular) * light_color * transmission * attenuation * weight;
+            }
         }
-        acc /= additions as f32;
-        return Point::new(acc.x as u8, acc.y as u8, acc.z as u8);
+        return acc + material.emission;
     }
 
-    fn is_clear_path(&self, scene: &Scene, point: &Point<f32>, light: &Light) -> bool {
-        let v = (light.origin - *point).to_normalized();
-        let to_light = Ray::new(*point, v);
+    /// The normal of the hit perturbed by the normal or bump map of its material
+    fn shading_normal(&self, scene: &Scene, hit: &Hit) -> Point<f32> {
+        let material = &scene.materials[hit.material];
+        let (Some(uv), Some((tangent, bitangent))) = (hit.uv, hit.tangents) else {
+            return hit.normal;
+        };
+        // tangent space around the interpolated normal
+        let normal = hit.normal;
+        let tangent = (tangent - normal * normal.dot(&tangent)).to_normalized();
+        let bitangent =
+            (bitangent - normal * normal.dot(&bitangent) - tangent * tangent.dot(&bitangent)).to_normalized();
 
-        let hit = self.hit(&scene, &to_light);
-        match &hit {
-            None => true,
-            Some(hit) => hit.dist * hit.dist > light.origin.distance2(point),
+        let perturbed = if let Some(normal_map) = material.normal_map {
+            let n = scene.textures[normal_map].sample_data(uv) * 2.0 - 1.0;
+            tangent * n.x + bitangent * n.y + normal * n.z
+        } else if let Some(bump_map) = material.bump_map {
+            let texture = &scene.textures[bump_map];
+            let [du, dv] = texture.texel_size();
+            let height = |u: f32, v: f32| texture.sample_data([u, v]).x;
+            let h = height(uv[0], uv[1]);
+            let dh_du = height(uv[0] + du, uv[1]) - h;
+            let dh_dv = height(uv[0], uv[1] + dv) - h;
+            normal - (tangent * dh_du + bitangent * dh_dv) * material.bump_scale
+        } else {
+            return hit.normal;
+        };
+        // a normal facing away from the viewer would make the surface black
+        match perturbed.dot(&normal) > 0.0 {
+            true => perturbed.to_normalized(),
+            false => hit.normal,
         }
     }
 
-    fn average_color(colors: &Vec<Point<u8>>) -> Point<u8> {
-        let mut final_color = Point::<u64>::homogeneous(0);
-        for color in colors {
-            final_color.x += color.x as u64;
-            final_color.y += color.y as u64;
-            final_color.z += color.z as u64;
+    /// Color seen along `ray`, None when it hits nothing. `depth` is the number of bounces so far
+    pub fn trace(&self, scene: &Scene, ray: &Ray, depth: usize) -> Option<Point<f32>> {
+        let hit = self.hit(scene, ray)?;
+        let material = &scene.materials[hit.material];
+        let color = self.get_color(scene, &hit);
+        let (reflectivity, transparency) = (material.reflectivity, material.transparency);
+        if (reflectivity <= 0.0 && transparency <= 0.0) || depth >= scene.settings.max_depth {
+            return Some(color);
         }
 
-        final_color.x /= colors.len() as u64;
-        final_color.y /= colors.len() as u64;
-        final_color.z /= colors.len() as u64;
+        let origin = offset_from_surface(hit.point, hit.normal);
+        let reflected = Ray::new(origin, reflect(ray.dir, hit.normal));
+        let reflection = self.trace(scene, &reflected, depth + 1).unwrap_or(scene.void());
+        let opaque = color * (1.0 - reflectivity) + reflection * reflectivity;
+        if transparency <= 0.0 {
+            return Some(opaque);
+        }
+        let dielectric = self.dielectric(scene, ray, &hit, material.ior, reflection, depth);
+        return Some(opaque * (1.0 - transparency) + dielectric * transparency);
+    }
+
+    /// The refracted ray mixed with the reflection by Schlick's Fresnel term, like glass or water
+    fn dielectric(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        hit: &Hit,
+        ior: f32,
+        reflection: Point<f32>,
+        depth: usize,
+    ) -> Point<f32> {
+        let (n1, n2) = match hit.front_face {
+            true => (1.0, ior),
+            false => (ior, 1.0),
+        };
+        let refracted = match refract(ray.dir, hit.normal, n1 / n2) {
+            Some(refracted) => refracted,
+            None => return reflection, // total internal reflection
+        };
+        let cos = match n1 <= n2 {
+            true => -ray.dir.dot(&hit.normal),
+            false => -refracted.dot(&hit.normal),
+        };
+        let fresnel = schlick(cos, n1, n2);
 
-        let final_color = Point::new(final_color.x as u8, final_color.y as u8, final_color.z as u8);
-        return final_color;
+        let origin = offset_from_surface(hit.point, -hit.normal);
+        let transmitted = Ray::new(origin, refracted);
+        let transmission = self.trace(scene, &transmitted, depth + 1).unwrap_or(scene.void());
+        return reflection * fresnel + transmission * (1.0 - fresnel);
     }
 
-    pub fn render(&self, scene: &Scene, camera: &Camera, x: f32, y: f32) -> Point<u8> {
-        let mut colors: Vec<Point<u8>> = Vec::new();
+    /// Fraction of the light from `to_light`, `distance` away, that reaches the hit: 1 for a clear path,
+    /// 0 when an opaque shape is in the way, every transparent shape in between lets through its transparency
+    fn light_transmission(&self, scene: &Scene, hit: &Hit, to_light: Point<f32>, distance: f32) -> f32 {
+        let mut point = offset_from_surface(hit.point, hit.normal);
+        let mut distance = distance; // infinite for directional lights, anything in the way blocks them
+        let mut transmission = 1.0;
+
+        for _ in 0..MAX_SHADOW_OCCLUDERS {
+            let ray = Ray::new(point, to_light);
+            let occluder = match self.hit(&scene, &ray) {
+                Some(occluder) if occluder.dist < distance => occluder,
+                _ => return transmission,
+            };
+            transmission *= scene.materials[occluder.material].transparency;
+            if transmission <= 0.0 {
+                return 0.0;
+            }
+            distance -= occluder.dist;
+            point = offset_from_surface(occluder.point, -occluder.normal);
+        }
+        return 0.0; // too many surfaces in the way to bother
+    }
+
+    fn average_color(colors: &Vec<Point<f32>>) -> Point<f32> {
+        let mut final_color = Point::homogeneous(0.0);
+        for color in colors {
+            final_color += *color;
+        }
+        return final_color / colors.len() as f32;
+    }
+
+    pub fn render(&self, scene: &Scene, camera: &Camera, x: f32, y: f32) -> Point<f32> {
+        let mut colors: Vec<Point<f32>> = Vec::new();
         colors.reserve(self.resolution.aa.get());
         let row_columns = self.resolution.aa.pixels_per_side();
 
@@ -94,61 +222,191 @@ impl Renderer {
                 let x = x + (sub_x as f32) / (row_columns as f32);
                 let y = y + (sub_y as f32) / (row_columns as f32);
                 let ray = self.ray_from_pixel(camera, x, y);
-                let hit = self.hit(scene, &ray);
-                match hit {
-                    Some(hit) => colors.push(self.get_color(scene, &hit)),
-                    None => (),
-                }
+                colors.push(self.trace(scene, &ray, 0).unwrap_or(scene.void()));
             }
         }
-        if colors.len() == 0 {
-            return scene.void();
-        }
-        return Self::average_color(&colors) + scene.ambient.absolute_color();
+        return Self::average_color(&colors);
     }
 }
 
+/// Renders `pixels` on `threads()` threads, the results arrive in the returned channel.
+/// `cameras` is passed separately from the scene so the window can move them without copying the scene.
+/// Dropping the receiver stops the threads after their current buffer
 pub fn render_multithreaded(
-    scene: &Scene,
+    scene: Arc<Scene>,
+    cameras: &[Camera],
     resolution: &Resolution,
     pixels: impl Iterator<Item = PixelReqBuffer> + Send + 'static,
-) -> impl Iterator<Item = PixelResBuffer> {
+) -> mpsc::Receiver<PixelResBuffer> {
     let (tx, rx) = mpsc::channel();
     let renderer = Arc::new(Renderer::new(resolution.clone()));
     let pixels = Arc::new(Mutex::new(pixels));
-    let scene = Arc::new(RwLock::new(scene.clone()));
+    let cameras = Arc::new(cameras.to_vec());
 
     for _ in 0..threads() {
         let tx = tx.clone();
         let renderer = renderer.clone();
         let scene = scene.clone();
+        let cameras = cameras.clone();
         let pixels_clone = pixels.clone();
 
-        std::thread::spawn(move || {
-            let scene = scene.read().unwrap();
-            loop {
-                let mut buffers = pixels_clone.lock().unwrap();
-                let buffer = buffers.next();
-                if buffer.is_none() {
-                    return;
-                }
-                let buffer = buffer.unwrap();
-                drop(buffers); // unlock mutex as soon as possible
-
-                let mut colors = [None; PIXEL_BUFFER_SIZE];
-                for (i, pixel) in buffer.into_iter().enumerate() {
-                    if let Some(pixel) = pixel {
-                        let color = renderer.render(&scene, &scene.camera, pixel.x as f32, pixel.y as f32);
-                        colors[i] = Some(PixelRes::new(pixel.x, pixel.y, color));
-                    }
-                }
-                if colors.iter().all(|c| c.is_none()) {
-                    return;
+        std::thread::spawn(move || loop {
+            let mut buffers = pixels_clone.lock().unwrap();
+            let buffer = buffers.next();
+            if buffer.is_none() {
+                return;
+            }
+            let buffer = buffer.unwrap();
+            drop(buffers); // unlock mutex as soon as possible
+
+            let mut colors = [None; PIXEL_BUFFER_SIZE];
+            for (i, pixel) in buffer.into_iter().enumerate() {
+                if let Some(pixel) = pixel {
+                    let camera = &cameras[pixel.camera];
+                    let color = renderer.render(&scene, camera, pixel.x as f32, pixel.y as f32);
+                    colors[i] = Some(PixelRes::new(pixel.x, pixel.y, pixel.camera, color));
                 }
-                tx.send(colors).unwrap();
+            }
+            if colors.iter().all(|c| c.is_none()) {
+                return;
+            }
+            if tx.send(colors).is_err() {
+                return; // nobody is waiting for the result anymore
             }
         });
     }
     drop(tx);
-    rx.into_iter()
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::light::{Falloff, Light};
+    use crate::material::Material;
+    use crate::num::{Float0to1, PositiveNonzeroF32};
+    use crate::plane::Plane;
+    use crate::primitive::Primitive;
+    use crate::resolution::Resolution;
+    use crate::scene_readers::test_scene;
+    use crate::sphere::Sphere;
+    use crate::texture::Texture;
+    use crate::util::{Hit, Ray};
+    use crate::vector::Point;
+    use image::{DynamicImage, GrayImage, RgbImage};
+
+    /// The point 0,0,0 of a white floor, seen at an angle
+    fn floor_color(lights: Vec<Light>, shapes: Vec<Primitive>) -> Point<f32> {
+        let floor = Primitive::Plane(Plane::new(Point::homogeneous(0.0), Point::new(0.0, 1.0, 0.0), 0));
+        let scene = test_scene(
+            [vec![floor], shapes].concat(),
+            vec![Material::matte(Point::homogeneous(255))],
+            lights,
+        );
+        let renderer = Renderer::new(Resolution::default());
+        let ray = Ray::new(Point::new(3.0, 5.0, 0.0), Point::new(-3.0, -5.0, 0.0).to_normalized());
+        renderer.trace(&scene, &ray, 0).unwrap()
+    }
+
+    #[test]
+    fn lights_add_up() {
+        let half = Float0to1::new(0.5).unwrap();
+        // full intensity at the floor
+        let light = |y, color| {
+            Light::new(Point::new(0.0, y, 0.0), half, color).with_falloff(Falloff::InverseSquare { distance: 2.0 })
+        };
+        let white = light(2.0, Point::homogeneous(255));
+        let red = light(2.0, Point::new(255, 0, 0));
+
+        let one = floor_color(vec![white.clone()], vec![]);
+        assert_eq!(one, Point::homogeneous(0.5));
+        // by default lights have their intensity 1 unit away
+        let default = Light::new(Point::new(0.0, 2.0, 0.0), half, Point::homogeneous(255));
+        assert_eq!(floor_color(vec![default], vec![]), one / 4.0);
+        assert_eq!(floor_color(vec![white.clone(), white.clone()], vec![]), one * 2.0);
+        assert_eq!(
+            floor_color(vec![white.clone(), red.clone()], vec![]),
+            Point::new(1.0, 0.5, 0.5)
+        );
+
+        // a blocked light takes nothing away from the others
+        let blocked = light(-2.0, Point::homogeneous(255));
+        assert_eq!(floor_color(vec![white.clone(), blocked.clone()], vec![]), one);
+        let ball = Primitive::Sphere(Sphere::new(
+            Point::new(0.0, 1.0, 0.0),
+            PositiveNonzeroF32::new(0.5).unwrap(),
+            0,
+        ));
+        assert_eq!(floor_color(vec![white, blocked], vec![ball]), Point::homogeneous(0.0));
+    }
+
+    #[test]
+    fn mirror_reflects_shapes() {
+        let mut mirror = Material::matte(Point::homogeneous(0));
+        mirror.reflectivity = 1.0;
+        let mut red = Material::matte(Point::homogeneous(0));
+        red.emission = Point::new(1.0, 0.0, 0.0);
+        let floor = Primitive::Plane(Plane::new(Point::homogeneous(0.0), Point::new(0.0, 1.0, 0.0), 0));
+        // where the ray from 3,5,0 goes after bouncing off 0,0,0
+        let ball = Primitive::Sphere(Sphere::new(
+            Point::new(-3.0, 5.0, 0.0),
+            PositiveNonzeroF32::new(1.0).unwrap(),
+            1,
+        ));
+        let mut scene = test_scene(vec![floor, ball], vec![mirror, red], vec![]);
+        let renderer = Renderer::new(Resolution::default());
+        let ray = Ray::new(Point::new(3.0, 5.0, 0.0), Point::new(-3.0, -5.0, 0.0).to_normalized());
+        assert_eq!(renderer.trace(&scene, &ray, 0), Some(Point::new(1.0, 0.0, 0.0)));
+
+        // without bounces the mirror shows its own black
+        scene.settings.max_depth = 0;
+        assert_eq!(renderer.trace(&scene, &ray, 0), Some(Point::homogeneous(0.0)));
+    }
+
+    /// Shading normal at `uv` of an upward facing surface whose texture coordinates run along x and z
+    fn shading_normal(material: Material, texture: DynamicImage, uv: [f32; 2]) -> Point<f32> {
+        let scene = test_scene(vec![], vec![material], vec![]).with_textures(vec![Texture::from_image(texture)]);
+        let up = Point::new(0.0, 1.0, 0.0);
+        let mut hit = Hit::new(1.0, up, Point::homogeneous(0.0), up, true, 0);
+        hit.uv = Some(uv);
+        hit.tangents = Some((Point::new(1.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0)));
+        Renderer::new(Resolution::default()).shading_normal(&scene, &hit)
+    }
+
+    #[test]
+    fn normal_maps() {
+        let mut material = Material::matte(Point::homogeneous(255));
+        material.normal_map = Some(0);
+        let normal_map = |texel| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, image::Rgb(texel)));
+
+        // 128 is just above the middle of 0..255
+        let flat = shading_normal(material.clone(), normal_map([128, 128, 255]), [0.3, 0.6]);
+        assert!((flat - Point::new(0.0, 1.0, 0.0)).length() < 0.01);
+
+        // 45 degrees toward the tangent
+        let tilted = shading_normal(material, normal_map([218, 128, 218]), [0.3, 0.6]);
+        assert!((tilted - Point::new(1.0, 1.0, 0.0).to_normalized()).length() < 0.01);
+    }
+
+    #[test]
+    fn bump_maps() {
+        let mut material = Material::matte(Point::homogeneous(255));
+        material.bump_map = Some(0);
+        // rises along u
+        let ramp = GrayImage::from_fn(4, 1, |x, _| image::Luma([x as u8 * 64]));
+        let ramp = DynamicImage::ImageLuma8(ramp);
+
+        let tilted = shading_normal(material.clone(), ramp.clone(), [0.375, 0.5]);
+        assert!((tilted.length() - 1.0).abs() < 1e-6);
+        assert!(tilted.x < -0.1, "{:?} should lean away from the slope", tilted);
+        assert!(tilted.z.abs() < 1e-6);
+
+        // a larger -bm tilts it further
+        material.bump_scale *= 2.0;
+        let steeper = shading_normal(material.clone(), ramp.clone(), [0.375, 0.5]);
+        assert!(steeper.x < tilted.x);
+
+        material.bump_scale = 0.0;
+        assert_eq!(shading_normal(material, ramp, [0.375, 0.5]), Point::new(0.0, 1.0, 0.0));
+    }
 }
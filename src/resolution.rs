@@ -1,18 +1,32 @@
 use crate::num::is_power_of_2;
 use num_integer::Roots;
+use serde::{Deserialize, Serialize};
 use std::num::NonZeroUsize;
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(try_from = "RawAALevel")]
 pub struct AALevel {
     aa: usize,
 }
 
+/// An `AALevel` as it is deserialized, before it is checked like `AALevel::new` does
+#[derive(Deserialize)]
+struct RawAALevel {
+    aa: usize,
+}
+
+impl TryFrom<RawAALevel> for AALevel {
+    type Error = String;
+
+    fn try_from(raw: RawAALevel) -> Result<Self, String> {
+        AALevel::new(raw.aa).ok_or_else(|| format!("aa {} is not 1, 4, 16 or 64", raw.aa))
+    }
+}
+
 impl AALevel {
+    /// `aa` must be a power of 4, the samples of a pixel are a square grid
     pub fn new(aa: usize) -> Option<Self> {
-        if aa == 1 {
-            return Some(Self { aa });
-        }
-        if is_power_of_2(aa) {
+        if is_power_of_2(aa) && aa.sqrt() * aa.sqrt() == aa {
             return Some(Self { aa });
         }
         return None;
@@ -26,7 +40,7 @@ impl AALevel {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct Resolution {
     pub width: NonZeroUsize,
     pub height: NonZeroUsize,
@@ -53,3 +67,30 @@ impl Resolution {
         println!("  subpixels: {}", self.width.get() * self.height.get() * self.aa.get());
     }
 }
+
+/// Used when neither the scene file nor the command line specify a resolution
+impl Default for Resolution {
+    fn default() -> Self {
+        Self::new(
+            NonZeroUsize::new(50).unwrap(),
+            NonZeroUsize::new(50).unwrap(),
+            AALevel::new(1).unwrap(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AALevel;
+
+    #[test]
+    fn deserialized_aa_is_checked() {
+        let deserialize = |aa: usize| {
+            let bytes = serde_cbor::to_vec(&AALevel { aa }).unwrap();
+            serde_cbor::from_slice::<AALevel>(&bytes)
+        };
+        assert_eq!(deserialize(16).unwrap().pixels_per_side(), 4);
+        assert!(deserialize(0).is_err());
+        assert!(deserialize(2).is_err());
+    }
+}
@@ -1,5 +1,10 @@
+use crate::material::Material;
 use crate::num::Float0to1;
 use crate::octree::Octree;
+use crate::primitive::Primitive;
+use crate::renderer::RenderSettings;
+use crate::resolution::Resolution;
+use crate::texture::Texture;
 use crate::triangle::Triangle;
 use crate::{camera::Camera, light::Light, vector::Point};
 use serde::{Deserialize, Serialize};
@@ -11,8 +16,11 @@ use std::time::Duration;
 
 mod cbor;
 mod obj;
+mod parse_error;
 mod rt;
 
+pub use parse_error::ParseMode;
+
 #[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub enum FileType {
     Rt,
@@ -22,35 +30,56 @@ pub enum FileType {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Scene {
-    pub camera: Camera, // TODO: should be plural
-    pub triangles: Octree<Triangle>,
+    pub cameras: Vec<Camera>, // never empty
+    pub shapes: Octree<Primitive>,
+    /// Shapes refer to these by index
+    pub materials: Vec<Material>,
+    /// Materials refer to these by index
+    pub textures: Vec<Texture>,
     pub lights: Vec<Light>,
     pub ambient: Light,
+    pub resolution: Option<Resolution>,
+    pub settings: RenderSettings,
     pub load_duration: Duration,
     pub file_type: FileType,
 }
 
 impl Scene {
     pub fn new(
-        camera: Camera,
-        triangles: Octree<Triangle>,
+        cameras: Vec<Camera>,
+        shapes: Octree<Primitive>,
         lights: Vec<Light>,
         ambient: Light,
+        resolution: Option<Resolution>,
         parse_duration: Duration,
         file_type: FileType,
     ) -> Self {
         Self {
-            camera,
-            triangles,
+            cameras,
+            shapes,
+            materials: vec![],
+            textures: vec![],
             lights,
             ambient,
+            resolution,
+            settings: RenderSettings::default(),
             load_duration: parse_duration,
             file_type,
         }
     }
 
+    /// Shapes refer to `materials` by index
+    pub fn with_materials(self, materials: Vec<Material>) -> Self {
+        Self { materials, ..self }
+    }
+
+    /// Materials refer to `textures` by index
+    pub fn with_textures(self, textures: Vec<Texture>) -> Self {
+        Self { textures, ..self }
+    }
+
     pub fn save_to_file(&self, path: &Path) -> Result<Size, String> {
-        if path.extension().unwrap() != "cbor" {
+        if path.extension().is_none_or(|e| e != "cbor") {
             return Err("File extension must be .cbor".to_string());
         }
         let file = File::create(path).map_err(|e| e.to_string())?;
@@ -60,25 +89,44 @@ impl Scene {
         Ok(Size::from_bytes(file_size))
     }
 
-    pub fn void(&self) -> Point<u8> {
-        Point::homogeneous(0)
+    pub fn void(&self) -> Point<f32> {
+        Point::homogeneous(0.0)
     }
 
     #[allow(dead_code)]
     pub fn print_stats(&self) {
         println!("Scene");
         println!("  Loaded in: {:?}", self.load_duration);
-        println!("  Triangles: {}", self.triangles.shapes_count());
+        let count = |f: fn(&Primitive) -> bool| self.shapes.iter().filter(|shape| f(shape)).count();
+        println!("  Triangles: {}", count(|s| matches!(s, Primitive::Triangle(_))));
+        println!("  Spheres  : {}", count(|s| matches!(s, Primitive::Sphere(_))));
+        println!("  Cylinders: {}", count(|s| matches!(s, Primitive::Cylinder(_))));
+        println!("  Squares  : {}", count(|s| matches!(s, Primitive::Square(_))));
+        println!("  Planes   : {}", count(|s| matches!(s, Primitive::Plane(_))));
+        println!("  Materials: {}", self.materials.len());
+        println!("  Textures : {}", self.textures.len());
         println!("  Lights   : {}", self.lights.len());
+        println!("  Cameras  : {}", self.cameras.len());
     }
 }
 
-pub fn read_scene(path: &Path) -> Result<Scene, String> {
-    return match path.extension().unwrap().to_str().unwrap() {
-        "rt" => rt::read_rt(&path),
+/// `mode` only applies to .rt files
+pub fn read_scene(path: &Path, mode: ParseMode) -> Result<Scene, String> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    return match extension {
+        "rt" => {
+            let (scene, warnings) = rt::read_rt(&path, mode).map_err(|e| e.to_string())?;
+            for warning in &warnings {
+                println!("WARN: {}", warning);
+            }
+            Ok(scene)
+        }
         "obj" => obj::read_obj(&path),
         "cbor" => cbor::read_cbor(&path),
-        _ => Err("Unknown file type".to_string()),
+        _ => Err(format!(
+            "{}: unknown file type, expected .rt, .obj or .cbor",
+            path.display()
+        )),
     };
 }
 
@@ -121,3 +169,24 @@ pub fn default_ambient() -> Light {
         Point::homogeneous(255),
     )
 }
+
+/// A scene without ambient light, seen from 0,5,0 looking down
+#[cfg(test)]
+pub fn test_scene(shapes: Vec<Primitive>, materials: Vec<Material>, lights: Vec<Light>) -> Scene {
+    let ambient = Light::new(
+        Point::homogeneous(0.0),
+        Float0to1::new(0.0).unwrap(),
+        Point::homogeneous(255),
+    );
+    let camera = Camera::new(Point::new(0.0, 5.0, 0.0), Point::new(0.0, -1.0, 0.0), 70.0, 1.0, 0.1);
+    return Scene::new(
+        vec![camera],
+        Octree::new(shapes),
+        lights,
+        ambient,
+        None,
+        Duration::ZERO,
+        FileType::Rt,
+    )
+    .with_materials(materials);
+}
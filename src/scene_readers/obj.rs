@@ -1,13 +1,16 @@
 use super::{default_ambient, look_at, FileType, Scene};
+use crate::camera::Camera;
 use crate::helpers::contains_duplicates;
 use crate::light::Light;
+use crate::material::{Material, MaterialStore, BASE_BUMP_SCALE};
 use crate::num::Float0to1;
 use crate::octree::Octree;
+use crate::primitive::Primitive;
+use crate::texture::TextureStore;
 use crate::triangle;
 use crate::triangle::Triangle;
 use crate::vector::Point;
-use image::{DynamicImage, GenericImageView, Pixel};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use tobj;
 
 pub fn read_obj(path: &Path) -> Result<Scene, String> {
@@ -19,60 +22,135 @@ pub fn read_obj(path: &Path) -> Result<Scene, String> {
     opt.single_index = false;
 
     let obj = tobj::load_obj(path, &opt);
-    let (models, _) = obj.expect("Failed to load OBJ file");
-    let texture = match get_texture(path) {
-        Some((path, texture)) => {
-            println!("Texture found: {}", path.display());
-            Some(texture)
-        }
-        None => None,
-    };
-    let triangles = parse_triangle(models, &texture)?;
+    let (models, obj_materials) = obj.map_err(|e| format!("{}: failed to load OBJ file: {}", path.display(), e))?;
+    let obj_materials = obj_materials.unwrap_or_else(|e| {
+        println!("WARN: {}: could not load materials: {}", path.display(), e);
+        vec![]
+    });
+    let mut textures = TextureStore::default();
+    let mesh_materials = obj_materials
+        .iter()
+        .map(|material| mesh_material(path, material, &mut textures))
+        .collect::<Vec<_>>();
+    let mut materials = MaterialStore::default();
+    let triangles = parse_triangle(models, &mesh_materials, &mut materials)?;
     if triangles.len() == 0 {
         return Err("No triangles found".into());
     }
 
     let camera = look_at(&triangles);
-    let mut lights: Vec<Light> = vec![];
-
-    lights.push(Light::new(
-        camera.pos,
-        Float0to1::new(0.5).unwrap(),
-        Point::new(255, 255, 255),
-    ));
-    let triangles = Octree::new(triangles);
+    let lights = vec![default_sun(&camera)];
+    let shapes = Octree::new(triangles.into_iter().map(Primitive::Triangle).collect());
     let parse_duration = now.elapsed();
     return Ok(Scene::new(
-        camera,
-        triangles,
+        vec![camera],
+        shapes,
         lights,
         default_ambient(),
+        None,
         parse_duration,
         FileType::Obj,
-    ));
+    )
+    .with_materials(materials.into_vec())
+    .with_textures(textures.into_vec()));
 }
 
-fn get_texture(obj_path: &Path) -> Option<(PathBuf, DynamicImage)> {
-    let mtl_path = obj_path.with_extension("mtl");
-    let mtl = tobj::load_mtl(mtl_path);
-    if let Err(_) = mtl {
-        return None;
+/// Sunlight from high up behind the camera, so everything in view is lit from the front and above
+fn default_sun(camera: &Camera) -> Light {
+    let direction = Point::new(camera.dir.x, -1.0, camera.dir.z);
+    Light::directional(direction, Float0to1::new(0.8).unwrap(), Point::new(255, 250, 240))
+}
+
+/// Used for meshes without a material
+const DEFAULT_COLOR: Point<u8> = Point { x: 200, y: 200, z: 200 };
+
+/// Maps a `tobj::Material` onto a scene material, its diffuse texture is added to `textures`
+fn mesh_material(obj_path: &Path, mtl: &tobj::Material, textures: &mut TextureStore) -> Material {
+    let rgb = |c: [f32; 3]| Point::new(c[0], c[1], c[2]);
+    let mut material = Material::matte(DEFAULT_COLOR);
+    if let Some(diffuse) = mtl.diffuse {
+        material.diffuse = rgb(diffuse);
+    }
+    if let Some(specular) = mtl.specular {
+        material.specular = rgb(specular);
+    }
+    if let Some(shininess) = mtl.shininess {
+        material.shininess = shininess.max(1.0); // 0 would light the whole surface
+    }
+    // Tr is the inverse of d used by some exporters
+    let transparency = match (mtl.dissolve, mtl.unknown_param.get("Tr")) {
+        (Some(dissolve), _) => Some(1.0 - dissolve),
+        (None, Some(tr)) => tr.parse::<f32>().ok(),
+        (None, None) => None,
+    };
+    if let Some(transparency) = transparency {
+        material.transparency = transparency.clamp(0.0, 1.0);
+    }
+    if let Some(ior) = mtl.optical_density.filter(|ior| *ior > 0.0) {
+        material.ior = ior;
+    }
+    if let Some(emission) = mtl.emissive {
+        material.emission = rgb(emission);
     }
-    let mut texture_path: Option<PathBuf> = None;
 
-    for mat in mtl.unwrap().0.iter() {
-        if let Some(ref path) = mat.diffuse_texture {
-            texture_path = Some(obj_path.with_file_name(path));
+    let mut load = |texture: &String| load_texture(obj_path, texture, textures);
+    material.texture = mtl.diffuse_texture.as_ref().and_then(&mut load);
+    // map_Bump and bump hold either kind, gray images are height maps. norm is always a normal map
+    let bump = mtl.normal_texture.as_ref().and_then(&mut load);
+    material.normal_map = mtl.unknown_param.get("norm").and_then(&mut load);
+    if let Some(bump) = bump {
+        match textures.is_grayscale(bump) {
+            true => material.bump_map = Some(bump),
+            false => material.normal_map = material.normal_map.or(Some(bump)),
         }
     }
+    let bump_options = mtl
+        .normal_texture
+        .as_deref()
+        .map_or(vec![], |texture| split_texture_options(texture).0);
+    if let Some((_, values)) = bump_options.iter().find(|(option, _)| *option == "-bm") {
+        let scale = values[0];
+        match scale.parse::<f32>() {
+            Ok(scale) if scale.is_finite() => material.bump_scale = BASE_BUMP_SCALE * scale,
+            _ => println!("WARN: invalid bump scale '{}' in material {}", scale, mtl.name),
+        }
+    }
+    material
+}
 
-    match texture_path {
-        Some(path) => {
-            let texture = image::open(&path).expect("Failed to open texture image");
-            return Some((path, texture));
+fn load_texture(obj_path: &Path, texture: &str, textures: &mut TextureStore) -> Option<usize> {
+    let path = obj_path.with_file_name(split_texture_options(texture).1);
+    textures.load(&path).map_err(|e| println!("WARN: {}", e)).ok()
+}
+
+/// Splits options like `-bm 0.5` or `-s 1 1 1` in front of the file name of a texture from the file name
+fn split_texture_options(texture: &str) -> (Vec<(&str, Vec<&str>)>, &str) {
+    fn next_word<'a>(texture: &mut &'a str) -> &'a str {
+        let (word, rest) = texture.split_once(char::is_whitespace).unwrap_or((texture, ""));
+        *texture = rest.trim_start();
+        word
+    }
+    let mut options = vec![];
+    let mut texture = texture.trim();
+    while texture.starts_with('-') {
+        let option = next_word(&mut texture);
+        // -mm takes a base and a gain, -o, -s and -t take u and optionally v and w
+        let max_values = match option {
+            "-mm" => 2,
+            "-o" | "-s" | "-t" => 3,
+            _ => 1,
+        };
+        let mut values = vec![next_word(&mut texture)];
+        while values.len() < max_values {
+            match texture.split_once(char::is_whitespace) {
+                // the last word is always part of the file name
+                Some((word, _)) if word.parse::<f32>().is_ok() => values.push(next_word(&mut texture)),
+                _ => break,
+            }
         }
-        None => None,
+        options.push((option, values));
     }
+    (options, texture)
 }
 
 struct MeshInfo {
@@ -80,7 +158,7 @@ struct MeshInfo {
     pub has_texture_coords: bool,
 }
 
-fn validate_mesh(mesh: &tobj::Mesh, texture: &Option<DynamicImage>) -> Result<MeshInfo, String> {
+fn validate_mesh(mesh: &tobj::Mesh, textured: bool) -> Result<MeshInfo, String> {
     let vertices = &mesh.positions;
     let vertices_i = &mesh.indices;
     let normals = &mesh.normals;
@@ -109,8 +187,8 @@ fn validate_mesh(mesh: &tobj::Mesh, texture: &Option<DynamicImage>) -> Result<Me
     if texture_coords_i.len() != 0 {
         info.has_texture_coords = true;
     }
-    if texture_coords_i.len() != 0 && texture.is_none() {
-        println!("WARN: Texture coordinates found but no texture");
+    if !textured {
+        // common for meshes whose material has no texture
         info.has_texture_coords = false;
     }
     if texture_coords_i.len() != 0 && texture_coords_i.len() != vertices_i.len() {
@@ -133,21 +211,21 @@ fn load_tri_vector(points: &Vec<f32>, indices: &Vec<u32>, i: usize) -> (Point<f3
     (p0, p1, p2)
 }
 
-fn get_texture_coordinate(model: &tobj::Model, vertex_i: usize, texture_w: u32, texture_h: u32) -> Option<(u32, u32)> {
+fn get_texture_coordinate(model: &tobj::Model, vertex_i: usize) -> Result<[f32; 2], String> {
     let texture_coords = &model.mesh.texcoords;
-    let texture_coords_i = &model.mesh.texcoord_indices;
-    let i = texture_coords_i[vertex_i] as usize;
+    let i = model.mesh.texcoord_indices[vertex_i] as usize;
     if i * 2 + 1 >= texture_coords.len() {
-        return None;
+        return Err("Texture coordinate index out of bounds".into());
     }
-    let u = texture_coords[i * 2];
-    let v = texture_coords[i * 2 + 1];
-    let x = (u * texture_w as f32) as u32;
-    let y = ((1.0 - v) * texture_h as f32) as u32;
-    Some((x, y))
+    Ok([texture_coords[i * 2], texture_coords[i * 2 + 1]])
 }
 
-fn parse_triangle(models: Vec<tobj::Model>, texture: &Option<DynamicImage>) -> Result<Vec<Triangle>, String> {
+/// Every mesh gets its material from `mesh.material_id`, textured meshes keep their texture coordinates
+fn parse_triangle(
+    models: Vec<tobj::Model>,
+    mesh_materials: &[Material],
+    materials: &mut MaterialStore,
+) -> Result<Vec<Triangle>, String> {
     let mut triangles: Vec<Triangle> = Vec::new();
 
     for (_, m) in models.iter().enumerate() {
@@ -155,7 +233,13 @@ fn parse_triangle(models: Vec<tobj::Model>, texture: &Option<DynamicImage>) -> R
         let vertices_i = &m.mesh.indices;
         let normals = &m.mesh.normals;
         let normals_i = &m.mesh.normal_indices;
-        let info = validate_mesh(&m.mesh, texture)?;
+        let mesh_material = m.mesh.material_id.and_then(|id| mesh_materials.get(id));
+        let material = match mesh_material {
+            Some(mesh_material) => materials.add(mesh_material.clone()),
+            None => materials.add(Material::matte(DEFAULT_COLOR)),
+        };
+        let textured = mesh_material.is_some_and(Material::is_textured);
+        let info = validate_mesh(&m.mesh, textured)?;
         let mut failed: usize = 0;
 
         if !info.has_vertex_normals {
@@ -168,22 +252,20 @@ fn parse_triangle(models: Vec<tobj::Model>, texture: &Option<DynamicImage>) -> R
                 failed += 1;
                 continue;
             }
-            // TODO, average the texture coordinates, not just use the first one
-            let color = match info.has_texture_coords {
-                true => {
-                    let (w, h) = texture.as_ref().unwrap().dimensions();
-                    let (x, y) = get_texture_coordinate(&m, i, w, h).unwrap();
-                    let color = texture.as_ref().unwrap().get_pixel(x, y).to_rgb();
-                    Point::new(color[0], color[1], color[2])
-                }
-                false => Point::new(255, 0, 0),
-            };
             let triangle = match info.has_vertex_normals {
                 true => {
                     let (n0, n1, n2) = load_tri_vector(normals, normals_i, i);
-                    triangle::Triangle::with_vertex_normals(p0, p1, p2, n0, n1, n2, color)
+                    triangle::Triangle::with_vertex_normals(p0, p1, p2, n0, n1, n2, material)
                 }
-                false => triangle::Triangle::new(p0, p1, p2, color),
+                false => triangle::Triangle::new(p0, p1, p2, material),
+            };
+            let triangle = match info.has_texture_coords {
+                true => triangle.with_uvs([
+                    get_texture_coordinate(m, i)?,
+                    get_texture_coordinate(m, i + 1)?,
+                    get_texture_coordinate(m, i + 2)?,
+                ]),
+                false => triangle,
             };
             triangles.push(triangle);
         }
@@ -197,3 +279,118 @@ fn parse_triangle(models: Vec<tobj::Model>, texture: &Option<DynamicImage>) -> R
     }
     Ok(triangles)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{mesh_material, read_obj, split_texture_options};
+    use crate::helpers::test_dir;
+    use crate::material::{Material, BASE_BUMP_SCALE};
+    use crate::primitive::Primitive;
+    use crate::texture::TextureStore;
+    use crate::vector::Point;
+    use image::{GrayImage, RgbImage};
+
+    #[test]
+    fn materials_per_mesh() {
+        let dir = test_dir("materials_per_mesh");
+        GrayImage::from_pixel(1, 1, image::Luma([128]))
+            .save(dir.join("height.png"))
+            .unwrap();
+        RgbImage::from_pixel(1, 1, image::Rgb([128, 128, 255]))
+            .save(dir.join("normal.png"))
+            .unwrap();
+        std::fs::write(
+            dir.join("two.mtl"),
+            "newmtl glass\nKd 0.1 0.2 0.3\nKs 1 1 1\nNs 200\nd 0.25\nNi 1.5\nmap_Bump -bm 0.5 height.png\n\n\
+             newmtl lamp\nKd 1 1 1\nKe 4 4 2\nbump normal.png\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("two.obj"),
+            "mtllib two.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n\
+             o plain\nf 1 3 4\no a\nusemtl glass\nf 1 2 3\no b\nusemtl lamp\nf 1 2 4\n",
+        )
+        .unwrap();
+
+        let scene = read_obj(&dir.join("two.obj")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut materials: Vec<_> = scene
+            .shapes
+            .iter()
+            .map(|shape| match shape {
+                Primitive::Triangle(triangle) => &scene.materials[triangle.material],
+                _ => panic!("OBJ files only contain triangles"),
+            })
+            .collect();
+        materials.sort_by(|a, b| a.diffuse.x.total_cmp(&b.diffuse.x));
+
+        let glass = materials[0];
+        assert_eq!(glass.diffuse, Point::new(0.1, 0.2, 0.3));
+        assert_eq!(glass.specular, Point::homogeneous(1.0));
+        assert_eq!(glass.shininess, 200.0);
+        assert_eq!(glass.transparency, 0.75);
+        assert_eq!(glass.ior, 1.5);
+        assert!(glass.bump_map.is_some() && glass.normal_map.is_none());
+        assert_eq!(glass.bump_scale, BASE_BUMP_SCALE * 0.5);
+
+        let plain = materials[1];
+        assert_eq!(plain.emission, Point::homogeneous(0.0));
+        assert_eq!(plain.specular, Point::homogeneous(0.0));
+
+        let lamp = materials[2];
+        assert_eq!(lamp.emission, Point::new(4.0, 4.0, 2.0));
+        assert!(lamp.normal_map.is_some() && lamp.bump_map.is_none());
+        assert_eq!(lamp.bump_scale, Material::matte(Point::homogeneous(0)).bump_scale);
+    }
+
+    #[test]
+    fn bump_multiplier() {
+        let dir = test_dir("bump_multiplier");
+        GrayImage::from_pixel(1, 1, image::Luma([128]))
+            .save(dir.join("height.png"))
+            .unwrap();
+        let mut textures = TextureStore::default();
+        let mut material = |bump: &str| {
+            let mtl = tobj::Material {
+                normal_texture: Some(bump.to_string()),
+                ..Default::default()
+            };
+            mesh_material(&dir.join("a.obj"), &mtl, &mut textures)
+        };
+        let plain = material("height.png");
+        assert_eq!(material("-bm 1 height.png"), plain);
+        assert_eq!(material("-bm 2 height.png").bump_scale, plain.bump_scale * 2.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn texture_options() {
+        assert_eq!(split_texture_options("bump.jpg"), (vec![], "bump.jpg"));
+        assert_eq!(
+            split_texture_options("-bm 0.5 bump map.jpg"),
+            (vec![("-bm", vec!["0.5"])], "bump map.jpg")
+        );
+        assert_eq!(
+            split_texture_options(" -bm 2  -clamp on  bump.jpg"),
+            (vec![("-bm", vec!["2"]), ("-clamp", vec!["on"])], "bump.jpg")
+        );
+        assert_eq!(
+            split_texture_options("-s 1 1 1 bump.png"),
+            (vec![("-s", vec!["1", "1", "1"])], "bump.png")
+        );
+        assert_eq!(
+            split_texture_options("-o 0.5 -bm 2 bump.png"),
+            (vec![("-o", vec!["0.5"]), ("-bm", vec!["2"])], "bump.png")
+        );
+        assert_eq!(
+            split_texture_options("-t 0.1 0.2 bump.png"),
+            (vec![("-t", vec!["0.1", "0.2"])], "bump.png")
+        );
+        assert_eq!(
+            split_texture_options("-mm 0 1 bump.png"),
+            (vec![("-mm", vec!["0", "1"])], "bump.png")
+        );
+        // a file name that looks like a number
+        assert_eq!(split_texture_options("-s 2 2 2"), (vec![("-s", vec!["2", "2"])], "2"));
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ParseMode {
+    /// The first malformed line fails the load
+    Strict,
+    /// Malformed lines are skipped and reported as warnings
+    Lenient,
+}
+
+/// Problem with one line of a scene file, `line` and `column` start at 1, 0 means the whole file
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+impl ParseError {
+    pub fn new(file: PathBuf, line: usize, column: usize, reason: String) -> Self {
+        Self {
+            file,
+            line,
+            column,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // errors about the file as a whole have no position
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.file.display(), self.reason);
+        }
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
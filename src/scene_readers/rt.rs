@@ -1,156 +1,700 @@
+use super::parse_error::{ParseError, ParseMode};
 use super::{FileType, Scene};
-use crate::light::Light;
-use crate::num::Float0to1;
+use crate::color;
+use crate::cylinder::Cylinder;
+use crate::light::{Falloff, Light, LightKind};
+use crate::material::{Material, MaterialStore};
+use crate::num::{Float0to1, PositiveNonzeroF32};
 use crate::octree::Octree;
+use crate::plane::Plane;
+use crate::primitive::Primitive;
+use crate::resolution::{AALevel, Resolution};
+use crate::sphere::Sphere;
+use crate::square::Square;
 use crate::vector::Point;
 use crate::{camera::Camera, triangle::Triangle};
 use std::io::{self, BufRead};
+use std::num::NonZeroUsize;
+use std::path::Path;
 
-fn parse_rgb(s: &str) -> Option<Point<u8>> {
-    let numbers: Vec<&str> = s.split(",").collect();
-    if numbers.iter().count() != 3 {
-        return None;
-    }
+const NORMALIZED_TOLERANCE: f32 = 1e-3;
 
-    let r = numbers[0].parse::<u8>().ok()?;
-    let g = numbers[1].parse::<u8>().ok()?;
-    let b = numbers[2].parse::<u8>().ok()?;
+/// Whitespace separated word of a line, `column` starts at 1
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
 
-    return Some(Point::new(r, g, b));
+/// Error within a single line, `read_rt` adds the file and line number
+#[derive(PartialEq, Debug)]
+struct LineError {
+    column: usize,
+    reason: String,
 }
 
-fn parse_point(s: &str, should_be_normalized: bool) -> Option<Point<f32>> {
-    let parts: Vec<&str> = s.split(",").collect();
-    if parts.len() != 3 {
-        return None;
+impl LineError {
+    fn new(column: usize, reason: impl Into<String>) -> Self {
+        Self {
+            column,
+            reason: reason.into(),
+        }
     }
-    let x = parts[0].parse::<f32>().ok()?;
-    let y = parts[1].parse::<f32>().ok()?;
-    let z = parts[2].parse::<f32>().ok()?;
+}
 
-    return match should_be_normalized {
-        true => Point::unit(x, y, z),
-        false => Some(Point::new(x, y, z)),
-    };
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    line.split_whitespace()
+        .map(|text| {
+            let offset = text.as_ptr() as usize - line.as_ptr() as usize;
+            Token {
+                text,
+                column: line[..offset].chars().count() + 1,
+            }
+        })
+        .collect()
+}
+
+/// `usage` lists the identifier and its arguments, optional arguments are wrapped in []
+fn expect_arguments(tokens: &[Token], usage: &str) -> Result<(), LineError> {
+    let expected: Vec<&str> = usage.split_whitespace().collect();
+    let required = expected.iter().filter(|arg| !arg.starts_with('[')).count();
+
+    if tokens.len() < required {
+        let last = tokens.last().unwrap();
+        let end_of_line = last.column + last.text.chars().count();
+        return Err(LineError::new(
+            end_of_line,
+            format!("missing {}, expected: {usage}", expected[tokens.len()]),
+        ));
+    }
+    if tokens.len() > expected.len() {
+        let extra = tokens[expected.len()];
+        return Err(LineError::new(
+            extra.column,
+            format!("unexpected argument '{}', expected: {usage}", extra.text),
+        ));
+    }
+    return Ok(());
 }
 
-fn parse_triangle(t: Vec<&str>) -> Option<Triangle> {
-    if t.len() != 5 {
-        return None;
+/// Splits `x,y,z` into 3 tokens, keeping track of their columns
+fn split_components<'a>(token: &Token<'a>, name: &str) -> Result<[Token<'a>; 3], LineError> {
+    let mut components = Vec::with_capacity(3);
+    let mut column = token.column;
+    for text in token.text.split(',') {
+        components.push(Token { text, column });
+        column += text.chars().count() + 1;
+    }
+    if components.len() != 3 {
+        return Err(LineError::new(
+            token.column,
+            format!(
+                "expected 3 comma-separated components for {name}, found {} in '{}'",
+                components.len(),
+                token.text
+            ),
+        ));
     }
-    if t[0] != "tr" {
-        return None;
+    return Ok([components[0], components[1], components[2]]);
+}
+
+fn parse_f32(token: &Token, name: &str) -> Result<f32, LineError> {
+    match token.text.parse::<f32>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(LineError::new(
+            token.column,
+            format!("{name} '{}' is not a number", token.text),
+        )),
     }
-    let v0 = parse_point(t[1], false)?;
-    let v1 = parse_point(t[2], false)?;
-    let v2 = parse_point(t[3], false)?;
-    let color = parse_rgb(t[4])?;
+}
+
+fn parse_positive(token: &Token, name: &str) -> Result<PositiveNonzeroF32, LineError> {
+    let n = parse_f32(token, name)?;
+    return PositiveNonzeroF32::new(n)
+        .ok_or_else(|| LineError::new(token.column, format!("{name} {n} must be greater than 0")));
+}
+
+fn parse_ratio(token: &Token, name: &str) -> Result<Float0to1, LineError> {
+    let n = parse_f32(token, name)?;
+    return Float0to1::new(n).ok_or_else(|| LineError::new(token.column, format!("{name} {n} outside [0,1]")));
+}
+
+fn parse_rgb(token: &Token) -> Result<Point<u8>, LineError> {
+    let [r, g, b] = split_components(token, "color")?;
+    let component = |c: Token| {
+        c.text.parse::<u8>().map_err(|_| match c.text.parse::<i64>() {
+            Ok(n) => LineError::new(c.column, format!("color component {n} outside [0,255]")),
+            Err(_) => LineError::new(c.column, format!("color component '{}' is not an integer", c.text)),
+        })
+    };
 
-    return Some(Triangle::new(v0, v1, v2, color));
+    return Ok(Point::new(component(r)?, component(g)?, component(b)?));
 }
 
-fn parse_light(blocks: Vec<&str>) -> Option<Light> {
-    if blocks.get(0) != Some(&"l") {
-        return None;
+fn parse_point(token: &Token, name: &str) -> Result<Point<f32>, LineError> {
+    let [x, y, z] = split_components(token, name)?;
+    return Ok(Point::new(
+        parse_f32(&x, name)?,
+        parse_f32(&y, name)?,
+        parse_f32(&z, name)?,
+    ));
+}
+
+fn parse_direction(token: &Token, name: &str) -> Result<Point<f32>, LineError> {
+    let point = parse_point(token, name)?;
+    // scene files are written with a limited number of decimals
+    let length = point.length();
+    if (length - 1.0).abs() > NORMALIZED_TOLERANCE {
+        return Err(LineError::new(
+            token.column,
+            format!("{name} {} is not normalized (length {length})", token.text),
+        ));
     }
+    return Ok(point.to_normalized());
+}
+
+fn parse_usize(token: &Token, name: &str) -> Result<usize, LineError> {
+    return token.text.parse::<usize>().map_err(|_| {
+        LineError::new(
+            token.column,
+            format!("{name} '{}' is not a positive integer", token.text),
+        )
+    });
+}
+
+fn parse_triangle(t: &[Token], material: &mut MaterialParser) -> Result<Triangle, LineError> {
+    expect_arguments(t, "tr <p0> <p1> <p2> <r,g,b>")?;
+    let v0 = parse_point(&t[1], "p0")?;
+    let v1 = parse_point(&t[2], "p1")?;
+    let v2 = parse_point(&t[3], "p2")?;
+    let material = material(parse_rgb(&t[4])?)?;
+
+    return Ok(Triangle::new(v0, v1, v2, material));
+}
+
+fn parse_sphere(blocks: &[Token], material: &mut MaterialParser) -> Result<Sphere, LineError> {
+    expect_arguments(blocks, "sp <center> <diameter> <r,g,b>")?;
+    let center = parse_point(&blocks[1], "center")?;
+    let diameter = parse_positive(&blocks[2], "diameter")?;
+    let material = material(parse_rgb(&blocks[3])?)?;
+
+    return Ok(Sphere::new(center, diameter, material));
+}
+
+fn parse_cylinder(blocks: &[Token], material: &mut MaterialParser) -> Result<Cylinder, LineError> {
+    expect_arguments(blocks, "cy <center> <axis> <diameter> <height> <r,g,b>")?;
+    let center = parse_point(&blocks[1], "center")?;
+    let axis = parse_direction(&blocks[2], "axis")?;
+    let diameter = parse_positive(&blocks[3], "diameter")?;
+    let height = parse_positive(&blocks[4], "height")?;
+    let material = material(parse_rgb(&blocks[5])?)?;
+
+    return Ok(Cylinder::new(center, axis, diameter, height, material));
+}
 
-    let origin = parse_point(blocks.get(1)?, false)?;
-    let intensity = blocks.get(2)?.parse::<f32>().ok()?;
-    let intensity = Float0to1::new(intensity)?;
-    let color = parse_rgb(blocks.get(3)?)?;
+fn parse_square(blocks: &[Token], material: &mut MaterialParser) -> Result<Square, LineError> {
+    expect_arguments(blocks, "sq <center> <normal> <side> <r,g,b>")?;
+    let center = parse_point(&blocks[1], "center")?;
+    let normal = parse_direction(&blocks[2], "normal")?;
+    let side = parse_positive(&blocks[3], "side")?;
+    let material = material(parse_rgb(&blocks[4])?)?;
 
-    return Some(Light::new(origin, intensity, color));
+    return Ok(Square::new(center, normal, side, material));
 }
 
-fn parse_camera(blocks: Vec<&str>) -> Option<Camera> {
-    if blocks.get(0) != Some(&"c") {
-        return None;
+fn parse_plane(blocks: &[Token], material: &mut MaterialParser) -> Result<Plane, LineError> {
+    expect_arguments(blocks, "pl <point> <normal> <r,g,b>")?;
+    let point = parse_point(&blocks[1], "point")?;
+    let normal = parse_direction(&blocks[2], "normal")?;
+    let material = material(parse_rgb(&blocks[3])?)?;
+
+    return Ok(Plane::new(point, normal, material));
+}
+
+/// Turns the color of a shape and the material parameters of its line into an index into `Scene.materials`
+type MaterialParser<'a> = dyn FnMut(Point<u8>) -> Result<usize, LineError> + 'a;
+
+/// Separates the optional `key=value` parameters from the other arguments
+fn split_parameters<'a>(tokens: &[Token<'a>]) -> (Vec<Token<'a>>, Vec<Token<'a>>) {
+    tokens.iter().partition(|token| !token.text.contains('='))
+}
+
+/// `specular=<r,g,b> shininess=<n> reflectivity=<0..1> transparency=<0..1> ior=<n> emission=<r,g,b>`, all optional
+fn parse_material(color: Point<u8>, params: &[Token], materials: &mut MaterialStore) -> Result<usize, LineError> {
+    let mut material = Material::matte(color);
+    for param in params {
+        let (key, value) = param.text.split_once('=').unwrap();
+        let value = Token {
+            text: value,
+            column: param.column + key.chars().count() + 1,
+        };
+        match key {
+            "specular" => material.specular = color::from_u8(parse_rgb(&value)?),
+            "shininess" => material.shininess = parse_positive(&value, key)?.get(),
+            "reflectivity" => material.reflectivity = parse_ratio(&value, key)?.get(),
+            "transparency" => material.transparency = parse_ratio(&value, key)?.get(),
+            "ior" => material.ior = parse_positive(&value, key)?.get(),
+            "emission" => material.emission = color::from_u8(parse_rgb(&value)?),
+            _ => {
+                return Err(LineError::new(
+                    param.column,
+                    format!(
+                        "unknown material parameter '{key}', \
+                        expected specular, shininess, reflectivity, transparency, ior or emission"
+                    ),
+                ))
+            }
+        }
+    }
+    return Ok(materials.add(material));
+}
+
+/// `falloff=<none|inverse_square|constant,linear,quadratic> falloff_distance=<n>`, all optional.
+/// Lights other than directional ones fall off with the inverse square by default,
+/// `falloff_distance` is where an inverse square light has its intensity, 1 by default
+fn parse_falloff(light: Light, params: &[Token]) -> Result<Light, LineError> {
+    let mut falloff = None;
+    let mut distance = None;
+    for param in params {
+        let (key, value) = param.text.split_once('=').unwrap();
+        let value = Token {
+            text: value,
+            column: param.column + key.chars().count() + 1,
+        };
+        match key {
+            "falloff" => {
+                falloff = Some(match value.text {
+                    "none" => Falloff::None,
+                    "inverse_square" => Falloff::InverseSquare { distance: 1.0 },
+                    _ => {
+                        let [constant, linear, quadratic] = split_components(&value, key)?;
+                        let coefficient = |token: &Token| match parse_f32(token, key)? {
+                            n if n < 0.0 => Err(LineError::new(token.column, format!("{key} {n} is negative"))),
+                            n => Ok(n),
+                        };
+                        let (constant, linear, quadratic) =
+                            (coefficient(&constant)?, coefficient(&linear)?, coefficient(&quadratic)?);
+                        if constant + linear + quadratic <= 0.0 {
+                            return Err(LineError::new(value.column, format!("{key} {} is all 0", value.text)));
+                        }
+                        Falloff::Polynomial {
+                            constant,
+                            linear,
+                            quadratic,
+                        }
+                    }
+                })
+            }
+            "falloff_distance" => distance = Some((parse_positive(&value, key)?.get(), param.column)),
+            _ => {
+                return Err(LineError::new(
+                    param.column,
+                    format!("unknown light parameter '{key}', expected falloff or falloff_distance"),
+                ))
+            }
+        }
     }
-    let origin = parse_point(blocks.get(1)?, false)?;
-    let direction = parse_point(blocks.get(2)?, true)?;
-    let fov = blocks.get(3)?.parse::<f32>().ok()?;
+    if let LightKind::Directional { .. } = light.kind {
+        if let Some(param) = params.iter().find(|p| p.text != "falloff=none") {
+            return Err(LineError::new(param.column, "directional lights do not fall off"));
+        }
+    }
+    let falloff = match (falloff.unwrap_or(light.falloff), distance) {
+        (Falloff::InverseSquare { .. }, Some((distance, _))) => Falloff::InverseSquare { distance },
+        (_, Some((_, column))) => return Err(LineError::new(column, "falloff_distance needs falloff=inverse_square")),
+        (falloff, None) => falloff,
+    };
+    return Ok(light.with_falloff(falloff));
+}
 
-    return Some(Camera::new(origin, direction, fov, 10.0, 0.15));
+fn parse_light(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "l <origin> <intensity> <r,g,b>")?;
+    let origin = parse_point(&blocks[1], "origin")?;
+    let intensity = parse_ratio(&blocks[2], "intensity")?;
+    let color = parse_rgb(&blocks[3])?;
+
+    return Ok(Light::new(origin, intensity, color));
 }
 
-fn parse_ambient(blocks: Vec<&str>) -> Option<Light> {
-    if blocks.get(0) != Some(&"A") {
-        return None;
+fn parse_directional_light(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "ld <direction> <intensity> <r,g,b>")?;
+    let direction = parse_direction(&blocks[1], "direction")?;
+    let intensity = parse_ratio(&blocks[2], "intensity")?;
+    let color = parse_rgb(&blocks[3])?;
+
+    return Ok(Light::directional(direction, intensity, color));
+}
+
+/// The angles are in degrees from the axis of the cone
+fn parse_spot_light(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "ls <origin> <direction> <inner> <outer> <intensity> <r,g,b>")?;
+    let origin = parse_point(&blocks[1], "origin")?;
+    let direction = parse_direction(&blocks[2], "direction")?;
+    let outer = parse_f32(&blocks[4], "outer")?;
+    if outer <= 0.0 || outer > 180.0 {
+        return Err(LineError::new(
+            blocks[4].column,
+            format!("outer {outer} outside (0,180]"),
+        ));
     }
-    if blocks.len() != 3 {
-        return None;
+    let inner = parse_f32(&blocks[3], "inner")?;
+    if inner < 0.0 || inner > outer {
+        return Err(LineError::new(
+            blocks[3].column,
+            format!("inner {inner} outside [0,{outer}]"),
+        ));
     }
-    let intensity = blocks.get(1)?.parse::<f32>().ok()?;
-    let color = parse_rgb(blocks.get(2)?)?;
+    let intensity = parse_ratio(&blocks[5], "intensity")?;
+    let color = parse_rgb(&blocks[6])?;
 
-    return Some(Light::new(Point::homogeneous(0.0), Float0to1::new(intensity)?, color));
+    return Ok(Light::spot(origin, direction, inner, outer, intensity, color));
 }
 
-pub fn read_rt(path: &std::path::Path) -> Result<super::Scene, String> {
-    let now = std::time::Instant::now();
-    if !path.display().to_string().ends_with(".rt") {
-        return Err("File must end with .rt".into());
+fn parse_rect_light(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "lr <center> <edge1> <edge2> <intensity> <r,g,b>")?;
+    let center = parse_point(&blocks[1], "center")?;
+    let edge1 = parse_point(&blocks[2], "edge1")?;
+    let edge2 = parse_point(&blocks[3], "edge2")?;
+    if edge1.cross(&edge2).length() <= 0.0 {
+        return Err(LineError::new(
+            blocks[3].column,
+            "edge1 and edge2 do not span a rectangle",
+        ));
     }
-    let file = std::fs::File::open(path).or(Err("Could not open file"))?;
-    let lines = io::BufReader::new(file).lines();
-    let mut triangles: Vec<Triangle> = Vec::new();
+    let intensity = parse_ratio(&blocks[4], "intensity")?;
+    let color = parse_rgb(&blocks[5])?;
 
-    let mut ambient = Light::new(
-        Point::homogeneous(0.0),
-        Float0to1::new(0.0).unwrap(),
-        Point::homogeneous(0),
-    );
-    let mut lights: Vec<Light> = Vec::new();
-    let mut camera = Camera::new(
-        Point::new(35.0, 18.0, 31.0),
-        Point::new(-0.7247, -0.18, -0.78087),
-        70.0,
-        10.0,
-        0.15,
-    );
-
-    for line in lines {
-        let line = line.map_err(|_| "Could not read line".to_string())?;
-
-        if line.len() == 0 {
-            continue;
+    return Ok(Light::rect(center, edge1, edge2, intensity, color));
+}
+
+fn parse_sphere_light(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "lb <center> <diameter> <intensity> <r,g,b>")?;
+    let center = parse_point(&blocks[1], "center")?;
+    let diameter = parse_positive(&blocks[2], "diameter")?;
+    let intensity = parse_ratio(&blocks[3], "intensity")?;
+    let color = parse_rgb(&blocks[4])?;
+
+    return Ok(Light::sphere(center, diameter.get(), intensity, color));
+}
+
+fn parse_camera(blocks: &[Token]) -> Result<Camera, LineError> {
+    expect_arguments(blocks, "c <origin> <direction> <fov>")?;
+    let origin = parse_point(&blocks[1], "origin")?;
+    let direction = parse_direction(&blocks[2], "direction")?;
+    let fov = parse_f32(&blocks[3], "fov")?;
+    if fov <= 0.0 || fov > 180.0 {
+        return Err(LineError::new(blocks[3].column, format!("fov {fov} outside (0,180]")));
+    }
+
+    return Ok(Camera::new(origin, direction, fov, 10.0, 0.15));
+}
+
+fn parse_ambient(blocks: &[Token]) -> Result<Light, LineError> {
+    expect_arguments(blocks, "A <intensity> <r,g,b>")?;
+    let intensity = parse_ratio(&blocks[1], "intensity")?;
+    let color = parse_rgb(&blocks[2])?;
+
+    return Ok(Light::new(Point::homogeneous(0.0), intensity, color));
+}
+
+fn parse_resolution(blocks: &[Token]) -> Result<Resolution, LineError> {
+    expect_arguments(blocks, "R <width> <height> [<aa>]")?;
+    let dimension = |token: &Token, name: &str| {
+        NonZeroUsize::new(parse_usize(token, name)?)
+            .ok_or_else(|| LineError::new(token.column, format!("{name} must be greater than 0")))
+    };
+    let width = dimension(&blocks[1], "width")?;
+    let height = dimension(&blocks[2], "height")?;
+    let aa = match blocks.get(3) {
+        Some(token) => {
+            let aa = parse_usize(token, "aa")?;
+            AALevel::new(aa).ok_or_else(|| LineError::new(token.column, format!("aa {aa} is not 1, 4, 16 or 64")))?
         }
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() == 0 {
-            continue;
+        None => AALevel::new(1).unwrap(),
+    };
+
+    return Ok(Resolution::new(width, height, aa));
+}
+
+/// Everything read from the lines so far
+struct RtScene {
+    shapes: Vec<Primitive>,
+    materials: MaterialStore,
+    lights: Vec<Light>,
+    cameras: Vec<Camera>,
+    ambient: Option<Light>,
+    resolution: Option<Resolution>,
+}
+
+impl RtScene {
+    fn new() -> Self {
+        Self {
+            shapes: Vec::new(),
+            materials: MaterialStore::default(),
+            lights: Vec::new(),
+            cameras: Vec::new(),
+            ambient: None,
+            resolution: None,
         }
-        match parts[0] {
-            "tr" => {
-                if let Some(t) = parse_triangle(parts) {
-                    triangles.push(t);
+    }
+
+    fn parse_line(&mut self, tokens: &[Token]) -> Result<(), LineError> {
+        let identifier = tokens[0];
+        let (tokens, params) = split_parameters(tokens);
+        let tokens = &tokens[..];
+        // shapes take material parameters and lights falloff parameters
+        let takes_params = ["tr", "sp", "cy", "sq", "pl", "l", "ld", "ls", "lr", "lb"].contains(&identifier.text);
+        if let (false, Some(param)) = (takes_params, params.first()) {
+            return Err(LineError::new(
+                param.column,
+                format!("'{}' does not take parameters", identifier.text),
+            ));
+        }
+        let materials = &mut self.materials;
+        let material = &mut |color| parse_material(color, &params, materials);
+        let falloff = |light| parse_falloff(light, &params);
+
+        match identifier.text {
+            "tr" => self.shapes.push(Primitive::Triangle(parse_triangle(tokens, material)?)),
+            "sp" => self.shapes.push(Primitive::Sphere(parse_sphere(tokens, material)?)),
+            "cy" => self.shapes.push(Primitive::Cylinder(parse_cylinder(tokens, material)?)),
+            "sq" => self.shapes.push(Primitive::Square(parse_square(tokens, material)?)),
+            "pl" => self.shapes.push(Primitive::Plane(parse_plane(tokens, material)?)),
+            "l" => self.lights.push(falloff(parse_light(tokens)?)?),
+            "ld" => self.lights.push(falloff(parse_directional_light(tokens)?)?),
+            "ls" => self.lights.push(falloff(parse_spot_light(tokens)?)?),
+            "lr" => self.lights.push(falloff(parse_rect_light(tokens)?)?),
+            "lb" => self.lights.push(falloff(parse_sphere_light(tokens)?)?),
+            "c" => self.cameras.push(parse_camera(tokens)?),
+            "A" => {
+                let ambient = parse_ambient(tokens)?;
+                if self.ambient.is_some() {
+                    return Err(LineError::new(
+                        identifier.column,
+                        "ambient light declared more than once",
+                    ));
                 }
+                self.ambient = Some(ambient);
             }
-            "l" => {
-                if let Some(l) = parse_light(parts) {
-                    lights.push(l);
+            "R" => {
+                let resolution = parse_resolution(tokens)?;
+                if self.resolution.is_some() {
+                    return Err(LineError::new(identifier.column, "resolution declared more than once"));
                 }
+                self.resolution = Some(resolution);
             }
-            "c" => {
-                if let Some(c) = parse_camera(parts) {
-                    camera = c;
-                }
+            unknown => {
+                return Err(LineError::new(
+                    identifier.column,
+                    format!("unknown identifier '{unknown}'"),
+                ))
             }
-            "A" => {
-                if let Some(a) = parse_ambient(parts) {
-                    ambient = a;
-                }
+        }
+        return Ok(());
+    }
+}
+
+/// Lines starting with # are comments.
+/// In `ParseMode::Lenient` malformed lines are skipped and returned as warnings
+pub fn read_rt(path: &Path, mode: ParseMode) -> Result<(Scene, Vec<ParseError>), ParseError> {
+    let now = std::time::Instant::now();
+    let error = |line: usize, column: usize, reason: String| ParseError::new(path.to_path_buf(), line, column, reason);
+    if !path.display().to_string().ends_with(".rt") {
+        return Err(error(0, 0, "file must end with .rt".into()));
+    }
+    let file = std::fs::File::open(path).map_err(|e| error(0, 0, format!("could not open file: {e}")))?;
+    let lines = io::BufReader::new(file).lines();
+    let mut rt = RtScene::new();
+    let mut warnings: Vec<ParseError> = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(|e| error(i + 1, 0, format!("could not read line: {e}")))?;
+        let tokens = tokenize(&line);
+        if tokens.is_empty() || tokens[0].text.starts_with('#') {
+            continue;
+        }
+        if let Err(e) = rt.parse_line(&tokens) {
+            let e = error(i + 1, e.column, e.reason);
+            match mode {
+                ParseMode::Strict => return Err(e),
+                ParseMode::Lenient => warnings.push(e),
             }
-            _ => (),
         }
     }
-    let triangles = Octree::new(triangles);
+
+    let ambient = rt.ambient.unwrap_or(Light::new(
+        Point::homogeneous(0.0),
+        Float0to1::new(0.0).unwrap(),
+        Point::homogeneous(0),
+    ));
+    let mut cameras = rt.cameras;
+    if cameras.is_empty() {
+        cameras.push(Camera::new(
+            Point::new(35.0, 18.0, 31.0),
+            Point::new(-0.7247, -0.18, -0.78087),
+            70.0,
+            10.0,
+            0.15,
+        ));
+    }
+    let shapes = Octree::new(rt.shapes);
     let parse_duration = now.elapsed();
-    return Ok(Scene::new(
-        camera,
-        triangles,
-        lights,
+    let scene = Scene::new(
+        cameras,
+        shapes,
+        rt.lights,
         ambient,
+        rt.resolution,
         parse_duration,
         FileType::Rt,
-    ));
+    )
+    .with_materials(rt.materials.into_vec());
+    return Ok((scene, warnings));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_rt, tokenize, LineError, RtScene};
+    use crate::light::Falloff;
+    use crate::scene_readers::parse_error::ParseMode;
+    use std::path::Path;
+
+    fn parse(line: &str) -> Result<(), LineError> {
+        RtScene::new().parse_line(&tokenize(line))
+    }
+
+    #[test]
+    fn tokenize_columns() {
+        let tokens = tokenize("sp  0,0,0\t1.5 255,0,0");
+        let columns: Vec<usize> = tokens.iter().map(|t| t.column).collect();
+        assert_eq!(columns, vec![1, 5, 11, 15]);
+    }
+
+    #[test]
+    fn valid_lines() {
+        assert_eq!(parse("sp 0,0,0 1.5 0,165,255"), Ok(()));
+        assert_eq!(parse("cy 0,1,0 0,0.9578261349,0.2873478405 3 5 0,255,0"), Ok(()));
+        assert_eq!(parse("R 1920 1080 4"), Ok(()));
+        assert_eq!(parse("ld 0,-1,0 0.8 255,240,220"), Ok(()));
+        assert_eq!(parse("ls 0,4,0 0,-1,0 15 25 1 255,255,255"), Ok(()));
+        assert_eq!(parse("lr 0,4,0 2,0,0 0,0,1 0.8 255,255,255"), Ok(()));
+        assert_eq!(parse("lb 0,4,0 0.5 0.8 255,255,255"), Ok(()));
+        assert_eq!(
+            parse("l 0,4,0 1 255,255,255 falloff=inverse_square falloff_distance=5"),
+            Ok(())
+        );
+        assert_eq!(parse("ls 0,4,0 0,-1,0 15 25 1 255,255,255 falloff=1,0.1,0.01"), Ok(()));
+        assert_eq!(parse("lb 0,4,0 0.5 0.8 255,255,255 falloff_distance=5"), Ok(()));
+        assert_eq!(parse("ld 0,-1,0 0.8 255,240,220 falloff=none"), Ok(()));
+        assert_eq!(
+            parse("sp 0,0,0 1.5 0,165,255 specular=255,255,255 shininess=64 ior=1.5"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse("sp 0,0 1.5 0,165,255").unwrap_err();
+        assert_eq!(error.column, 4);
+        assert!(error.reason.contains("expected 3 comma-separated components"));
+
+        let error = parse("l 2,4.5,10 1.3 255,255,255").unwrap_err();
+        assert_eq!(error, LineError::new(12, "intensity 1.3 outside [0,1]"));
+
+        let error = parse("ld 0,-2,0 0.8 255,255,255").unwrap_err();
+        assert_eq!(
+            error,
+            LineError::new(4, "direction 0,-2,0 is not normalized (length 2)")
+        );
+
+        let error = parse("ls 0,4,0 0,-1,0 30 25 1 255,255,255").unwrap_err();
+        assert_eq!(error, LineError::new(17, "inner 30 outside [0,25]"));
+
+        let error = parse("lr 0,4,0 2,0,0 4,0,0 0.8 255,255,255").unwrap_err();
+        assert_eq!(error, LineError::new(16, "edge1 and edge2 do not span a rectangle"));
+
+        let error = parse("R 1920 1080 2").unwrap_err();
+        assert_eq!(error, LineError::new(13, "aa 2 is not 1, 4, 16 or 64"));
+
+        let error = parse("A 0.5 255,256,255").unwrap_err();
+        assert_eq!(error, LineError::new(11, "color component 256 outside [0,255]"));
+
+        let error = parse("pl 0,0,0 0,2,0 255,255,255").unwrap_err();
+        assert_eq!(error.column, 10);
+
+        let error = parse("sp 0,0,0 1.5").unwrap_err();
+        assert_eq!(error.column, 13);
+        assert!(error.reason.starts_with("missing <r,g,b>"));
+
+        let error = parse("sp 0,0,0 1.5 0,165,255 reflectivity=2").unwrap_err();
+        assert_eq!(error, LineError::new(37, "reflectivity 2 outside [0,1]"));
+
+        let error = parse("sp 0,0,0 1.5 0,165,255 shiny=1").unwrap_err();
+        assert_eq!(error.column, 24);
+        assert!(error.reason.starts_with("unknown material parameter 'shiny'"));
+
+        let error = parse("c 0,0,0 0,0,1 70 shininess=3").unwrap_err();
+        assert_eq!(error, LineError::new(18, "'c' does not take parameters"));
+
+        let error = parse("l 0,0,0 1 255,255,255 shininess=3").unwrap_err();
+        assert_eq!(error.column, 23);
+        assert!(error.reason.starts_with("unknown light parameter 'shininess'"));
+
+        let error = parse("l 0,0,0 1 255,255,255 falloff=1,-1,0").unwrap_err();
+        assert_eq!(error, LineError::new(33, "falloff -1 is negative"));
+
+        let error = parse("l 0,0,0 1 255,255,255 falloff=none falloff_distance=3").unwrap_err();
+        assert_eq!(
+            error,
+            LineError::new(36, "falloff_distance needs falloff=inverse_square")
+        );
+
+        let error = parse("ld 0,-1,0 1 255,255,255 falloff=inverse_square").unwrap_err();
+        assert_eq!(error, LineError::new(25, "directional lights do not fall off"));
+
+        let error = parse("ld 0,-1,0 1 255,255,255 falloff_distance=2").unwrap_err();
+        assert_eq!(error, LineError::new(25, "directional lights do not fall off"));
+
+        let error = parse("  spp 0,0,0 1.5 0,165,255").unwrap_err();
+        assert_eq!(error, LineError::new(3, "unknown identifier 'spp'"));
+    }
+
+    #[test]
+    fn default_falloff() {
+        let falloffs = |lines: &[&str]| {
+            let mut scene = RtScene::new();
+            for line in lines {
+                scene.parse_line(&tokenize(line)).unwrap();
+            }
+            scene.lights.iter().map(|light| light.falloff).collect::<Vec<_>>()
+        };
+        let falloffs = falloffs(&[
+            "l 0,4,0 1 255,255,255",
+            "ls 0,4,0 0,-1,0 15 25 1 255,255,255",
+            "lr 0,4,0 2,0,0 0,0,1 0.8 255,255,255 falloff_distance=3",
+            "lb 0,4,0 0.5 0.8 255,255,255 falloff=none",
+            "ld 0,-1,0 0.8 255,240,220",
+        ]);
+        assert_eq!(
+            falloffs,
+            vec![
+                Falloff::InverseSquare { distance: 1.0 },
+                Falloff::InverseSquare { distance: 1.0 },
+                Falloff::InverseSquare { distance: 3.0 },
+                Falloff::None,
+                Falloff::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn bundled_scenes_have_no_warnings() {
+        for path in glob::glob("rt*/*.rt").unwrap() {
+            let path = path.unwrap();
+            let (_, warnings) = read_rt(&path, ParseMode::Lenient).unwrap();
+            assert_eq!(warnings, vec![], "{}", path.display());
+            assert!(read_rt(&path, ParseMode::Strict).is_ok());
+        }
+        assert!(read_rt(Path::new("does_not_exist.rt"), ParseMode::Lenient).is_err());
+    }
 }
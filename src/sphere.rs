@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::num::PositiveNonzeroF32;
+use crate::octree::AABB;
+use crate::util::{correct_normal, Hit, Intersect, Ray, Shape, RAY_EPSILON};
+use crate::vector::Point;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Sphere {
+    pub center: Point<f32>,
+    pub radius: f32,
+    /// Index into `Scene.materials`
+    pub material: usize,
+}
+
+impl Sphere {
+    pub fn new(center: Point<f32>, diameter: PositiveNonzeroF32, material: usize) -> Self {
+        Self {
+            center,
+            radius: diameter.get() / 2.0,
+            material,
+        }
+    }
+
+    /// Distance along the ray to the closest intersection in front of the origin
+    fn intersect(&self, ray: &Ray) -> Option<f32> {
+        // ray.dir is normalized, so the quadratic simplifies to t² + 2bt + c = 0
+        let oc = ray.origin - self.center;
+        let b = oc.dot(&ray.dir);
+        let c = oc.length2() - self.radius * self.radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt = discriminant.sqrt();
+
+        let near = -b - sqrt;
+        if near > RAY_EPSILON {
+            return Some(near);
+        }
+        // the origin is inside the sphere
+        let far = -b + sqrt;
+        if far > RAY_EPSILON {
+            return Some(far);
+        }
+        None
+    }
+}
+
+impl Intersect for Sphere {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.intersect(ray)?;
+        let point = ray.origin + ray.dir * t;
+        let normal = (point - self.center) / self.radius;
+        let (normal, front_face) = correct_normal(normal, &ray.dir);
+        Some(Hit::new(t, ray.origin, point, normal, front_face, self.material))
+    }
+}
+
+impl Shape for Sphere {
+    fn is_inside_aabb(&self, aabb: &AABB) -> bool {
+        aabb.is_inside_all(&[self.center - self.radius, self.center + self.radius])
+    }
+
+    fn aabb(&self) -> AABB {
+        AABB::new(self.center - self.radius, self.center + self.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::PositiveNonzeroF32;
+    use crate::sphere::Sphere;
+    use crate::util::{Intersect, Ray};
+    use crate::vector::Point;
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(Point::new(0.0, 0.0, 0.0), PositiveNonzeroF32::new(2.0).unwrap(), 0)
+    }
+
+    #[test]
+    fn hit_from_outside() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Point::new(0.0, 0.0, 1.0));
+        let hit = unit_sphere().hit(&ray).unwrap();
+        assert!((hit.dist - 4.0).abs() < 1e-5);
+        assert!(hit.point.approx_eq(&Point::new(0.0, 0.0, -1.0), 1e-5));
+        assert!(hit.normal.approx_eq(&Point::new(0.0, 0.0, -1.0), 1e-5));
+    }
+
+    #[test]
+    fn hit_from_inside() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+        let hit = unit_sphere().hit(&ray).unwrap();
+        assert!((hit.dist - 1.0).abs() < 1e-5);
+        // the normal faces the ray
+        assert!(hit.normal.approx_eq(&Point::new(0.0, -1.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn miss() {
+        let ray = Ray::new(Point::new(0.0, 2.0, -5.0), Point::new(0.0, 0.0, 1.0));
+        assert!(unit_sphere().hit(&ray).is_none());
+
+        let behind = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, 1.0));
+        assert!(unit_sphere().hit(&behind).is_none());
+    }
+}
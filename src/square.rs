@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::num::PositiveNonzeroF32;
+use crate::octree::AABB;
+use crate::util::{correct_normal, Hit, Intersect, Ray, Shape, RAY_EPSILON};
+use crate::vector::Point;
+
+/// Square in the plane through `center` perpendicular to `normal`.
+/// Its edges follow `u` and `v`, which are derived from the normal the same way the camera derives its axes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Square {
+    pub center: Point<f32>,
+    pub normal: Point<f32>,
+    pub side: f32,
+    /// Index into `Scene.materials`
+    pub material: usize,
+    u: Point<f32>,
+    v: Point<f32>,
+}
+
+impl Square {
+    pub fn new(center: Point<f32>, normal: Point<f32>, side: PositiveNonzeroF32, material: usize) -> Self {
+        let normal = normal.to_normalized();
+        let u = if normal.x == 0.0 && normal.z == 0.0 {
+            Point::new(1.0, 0.0, 0.0)
+        } else {
+            normal.cross(&Point::new(0.0, 1.0, 0.0)).to_normalized()
+        };
+        let v = normal.cross(&u).to_normalized();
+        Self {
+            center,
+            normal,
+            side: side.get(),
+            material,
+            u,
+            v,
+        }
+    }
+
+    fn corners(&self) -> [Point<f32>; 4] {
+        let u = self.u * (self.side / 2.0);
+        let v = self.v * (self.side / 2.0);
+        [
+            self.center + u + v,
+            self.center + u - v,
+            self.center - u - v,
+            self.center - u + v,
+        ]
+    }
+}
+
+impl Intersect for Square {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let denominator = self.normal.dot(&ray.dir);
+        if denominator.abs() < f32::EPSILON {
+            return None; // Ray is parallel to the square
+        }
+        let t = (self.center - ray.origin).dot(&self.normal) / denominator;
+        if t < RAY_EPSILON {
+            return None;
+        }
+        let point = ray.origin + ray.dir * t;
+        let local = point - self.center;
+        let half = self.side / 2.0;
+        if local.dot(&self.u).abs() > half || local.dot(&self.v).abs() > half {
+            return None;
+        }
+        let (normal, front_face) = correct_normal(self.normal, &ray.dir);
+        Some(Hit::new(t, ray.origin, point, normal, front_face, self.material))
+    }
+}
+
+impl Shape for Square {
+    fn is_inside_aabb(&self, aabb: &AABB) -> bool {
+        aabb.is_inside_all(&self.corners())
+    }
+
+    fn aabb(&self) -> AABB {
+        AABB::from_points(&self.corners())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::PositiveNonzeroF32;
+    use crate::square::Square;
+    use crate::util::{Intersect, Ray, Shape};
+    use crate::vector::Point;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn tilted() -> Square {
+        Square::new(
+            Point::new(0.0, 0.0, -4.0),
+            Point::new(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+            PositiveNonzeroF32::new(2.0).unwrap(),
+            0,
+        )
+    }
+
+    #[test]
+    fn hit() {
+        let ray = Ray::new(Point::new(0.5, 0.0, 0.0), Point::new(0.0, 0.0, -1.0));
+        let hit = tilted().hit(&ray).unwrap();
+        assert!((hit.dist - 4.0).abs() < 1e-5);
+        assert!(hit
+            .normal
+            .approx_eq(&Point::new(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2), 1e-5));
+    }
+
+    #[test]
+    fn miss_outside_edges() {
+        let ray = Ray::new(Point::new(1.1, 0.0, 0.0), Point::new(0.0, 0.0, -1.0));
+        assert!(tilted().hit(&ray).is_none());
+
+        // inside the plane, but further along the tilted edge than side / 2
+        let ray = Ray::new(Point::new(0.0, 0.75, 0.0), Point::new(0.0, 0.0, -1.0));
+        assert!(tilted().hit(&ray).is_none());
+    }
+
+    #[test]
+    fn corners_are_inside_aabb() {
+        let square = tilted();
+        let aabb = square.aabb();
+        assert!(square.is_inside_aabb(&aabb));
+        assert!(aabb.is_inside(&Point::new(1.0, FRAC_1_SQRT_2, -4.0 - FRAC_1_SQRT_2)));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::vector::Point;
+
+/// An RGB image that materials refer to by their index into `Scene.textures`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "RawTexture")]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    /// sRGB encoded, row by row from the top left
+    #[serde(with = "bytes")]
+    texels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn from_image(image: DynamicImage) -> Self {
+        let image = image.into_rgb8();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            texels: image.into_raw(),
+        }
+    }
+
+    /// Texel at `x`, `y`, coordinates outside the texture wrap around
+    fn texel(&self, x: i64, y: i64) -> Point<u8> {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        let i = (y * self.width as usize + x) * 3;
+        Point::new(self.texels[i], self.texels[i + 1], self.texels[i + 2])
+    }
+
+    /// Bilinear interpolation of `decode` of the four texels around `uv`, v points up like in OBJ files.
+    /// The texture repeats outside 0..1
+    fn bilinear(&self, uv: [f32; 2], decode: impl Fn(Point<u8>) -> Point<f32>) -> Point<f32> {
+        // texel centers are at .5
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = (1.0 - uv[1]) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let texel = |x, y| decode(self.texel(x, y));
+
+        let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1, y0) * tx;
+        let bottom = texel(x0, y0 + 1) * (1.0 - tx) + texel(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    /// Linear color at the texture coordinate `uv`
+    pub fn sample(&self, uv: [f32; 2]) -> Point<f32> {
+        self.bilinear(uv, color::from_u8)
+    }
+
+    /// Texel values at `uv` mapped to 0..1 without decoding sRGB, for normal and height maps
+    pub fn sample_data(&self, uv: [f32; 2]) -> Point<f32> {
+        self.bilinear(uv, |texel| {
+            Point::new(texel.x as f32, texel.y as f32, texel.z as f32) / 255.0
+        })
+    }
+
+    /// Size of one texel in texture coordinates
+    pub fn texel_size(&self) -> [f32; 2] {
+        [1.0 / self.width as f32, 1.0 / self.height as f32]
+    }
+}
+
+/// A `Texture` as it is deserialized, before its size is checked against the texels
+#[derive(Deserialize)]
+struct RawTexture {
+    width: u32,
+    height: u32,
+    #[serde(with = "bytes")]
+    texels: Vec<u8>,
+}
+
+impl TryFrom<RawTexture> for Texture {
+    type Error = String;
+
+    fn try_from(raw: RawTexture) -> Result<Self, String> {
+        if raw.width == 0 || raw.height == 0 {
+            return Err(format!("texture of {}x{} texels is empty", raw.width, raw.height));
+        }
+        let len = (raw.width as usize)
+            .checked_mul(raw.height as usize)
+            .and_then(|n| n.checked_mul(3));
+        if len != Some(raw.texels.len()) {
+            return Err(format!(
+                "texture of {}x{} texels has {} bytes of texels",
+                raw.width,
+                raw.height,
+                raw.texels.len()
+            ));
+        }
+        Ok(Self {
+            width: raw.width,
+            height: raw.height,
+            texels: raw.texels,
+        })
+    }
+}
+
+/// Collects the textures of a scene while it is read, every file is loaded once
+#[derive(Default)]
+pub struct TextureStore {
+    textures: Vec<Texture>,
+    indices: HashMap<PathBuf, usize>,
+    /// The files had no color channels, textures are always converted to RGB
+    grayscale: Vec<bool>,
+}
+
+impl TextureStore {
+    /// Returns the index to store in the material
+    pub fn load(&mut self, path: &Path) -> Result<usize, String> {
+        // materials may name the same file through different relative paths
+        let key = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let image = image::open(path).map_err(|e| format!("could not open texture {}: {}", path.display(), e))?;
+        self.grayscale.push(!image.color().has_color());
+        self.textures.push(Texture::from_image(image));
+        self.indices.insert(key, self.textures.len() - 1);
+        Ok(self.textures.len() - 1)
+    }
+
+    /// Height maps are stored as gray images, normal maps as color images
+    pub fn is_grayscale(&self, index: usize) -> bool {
+        self.grayscale[index]
+    }
+
+    pub fn into_vec(self) -> Vec<Texture> {
+        self.textures
+    }
+}
+
+/// Serializes the texels as one byte string instead of a sequence of numbers, which is smaller and much faster in CBOR
+mod bytes {
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a byte string")
+        }
+
+        fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(bytes)
+        }
+
+        // formats without byte strings write a sequence
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Texture, TextureStore};
+    use crate::color;
+    use crate::helpers::test_dir;
+    use crate::vector::Point;
+    use image::{DynamicImage, GrayImage, RgbImage};
+
+    #[test]
+    fn bilinear_sampling() {
+        // black on the left, white on the right
+        let image = RgbImage::from_fn(2, 2, |x, _| image::Rgb([x as u8 * 255; 3]));
+        let texture = Texture::from_image(DynamicImage::ImageRgb8(image));
+
+        assert_eq!(texture.sample([0.25, 0.5]), Point::homogeneous(0.0));
+        assert_eq!(texture.sample([0.75, 0.5]), Point::homogeneous(1.0));
+        assert_eq!(texture.sample([0.5, 0.5]), Point::homogeneous(0.5));
+        // wraps around at the edges
+        assert_eq!(texture.sample([1.0, 0.5]), Point::homogeneous(0.5));
+        assert_eq!(texture.sample([1.25, 0.5]), texture.sample([0.25, 0.5]));
+
+        let gray = color::from_u8(Point::homogeneous(128));
+        let image = RgbImage::from_pixel(3, 3, image::Rgb([128; 3]));
+        let texture = Texture::from_image(DynamicImage::ImageRgb8(image));
+        assert!((texture.sample([0.3, 0.7]) - gray).length() < 1e-6);
+    }
+
+    #[test]
+    fn store_loads_every_file_once() {
+        let dir = test_dir("store_loads_every_file_once");
+        RgbImage::from_pixel(2, 1, image::Rgb([255, 0, 0]))
+            .save(dir.join("red.png"))
+            .unwrap();
+        RgbImage::from_pixel(1, 2, image::Rgb([0, 0, 255]))
+            .save(dir.join("blue.png"))
+            .unwrap();
+        GrayImage::from_pixel(1, 1, image::Luma([128]))
+            .save(dir.join("gray.png"))
+            .unwrap();
+        // gray texels in a color file
+        RgbImage::from_pixel(1, 1, image::Rgb([128; 3]))
+            .save(dir.join("rgb_gray.png"))
+            .unwrap();
+
+        let mut store = TextureStore::default();
+        let red = store.load(&dir.join("red.png")).unwrap();
+        let blue = store.load(&dir.join("blue.png")).unwrap();
+        assert_eq!(store.load(&dir.join("red.png")).unwrap(), red);
+        assert_eq!(store.load(&dir.join(".").join("red.png")).unwrap(), red);
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        assert_eq!(store.load(&dir.join("sub").join("..").join("red.png")).unwrap(), red);
+        assert_ne!(red, blue);
+        assert!(store.load(&dir.join("missing.png")).is_err());
+        let gray = store.load(&dir.join("gray.png")).unwrap();
+        let rgb_gray = store.load(&dir.join("rgb_gray.png")).unwrap();
+        assert!(store.is_grayscale(gray));
+        assert!(!store.is_grayscale(rgb_gray));
+        assert!(!store.is_grayscale(red));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let textures = store.into_vec();
+        assert_eq!(textures.len(), 4);
+        let bytes = serde_cbor::to_vec(&textures).unwrap();
+        let textures: Vec<Texture> = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!((textures[red].width, textures[red].height), (2, 1));
+        assert_eq!(textures[blue].sample([0.5, 0.5]), Point::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn invalid_textures_are_not_deserialized() {
+        let deserialize = |width, height, texels: usize| {
+            let texture = Texture {
+                width,
+                height,
+                texels: vec![0; texels],
+            };
+            serde_cbor::from_slice::<Texture>(&serde_cbor::to_vec(&texture).unwrap())
+        };
+        assert!(deserialize(2, 3, 18).is_ok());
+        assert!(deserialize(2, 3, 17).is_err());
+        assert!(deserialize(2, 3, 19).is_err());
+        assert!(deserialize(0, 3, 0).is_err());
+        assert!(deserialize(2, 0, 0).is_err());
+    }
+}
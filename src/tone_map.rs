@@ -0,0 +1,102 @@
+use strum_macros::{Display, EnumIter, EnumString};
+
+use crate::color;
+use crate::vector::Point;
+
+/// How linear colors above 1.0 are brought into the displayable range
+#[derive(Debug, PartialEq, Clone, Copy, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum ToneMap {
+    /// Everything above 1.0 becomes white
+    Clamp,
+    /// x / (1 + x), never reaches white
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference transform, with a toe and a soft shoulder
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(&self, c: f32) -> f32 {
+        match self {
+            ToneMap::Clamp => c.clamp(0.0, 1.0),
+            ToneMap::Reinhard => c.max(0.0) / (1.0 + c.max(0.0)),
+            ToneMap::Aces => {
+                // Krzysztof Narkowicz's fit
+                let c = c.max(0.0);
+                let (a, b, c2, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((c * (a * c + b)) / (c * (c2 * c + d) + e)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Turns the linear colors of the frame buffer into 8 bit colors for the window and 8 bit image formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayTransform {
+    pub tone_map: ToneMap,
+    /// In stops, every stop doubles the brightness
+    pub exposure: f32,
+    /// Encode with the sRGB curve, otherwise the tone mapped values are written as they are
+    pub srgb: bool,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+            srgb: true,
+        }
+    }
+}
+
+impl DisplayTransform {
+    pub fn apply(&self, color: Point<f32>) -> Point<u8> {
+        let scale = 2f32.powf(self.exposure);
+        let channel = |c: f32| {
+            let c = self.tone_map.apply(c * scale);
+            match self.srgb {
+                true => color::srgb_encode(c),
+                false => c,
+            }
+        };
+        color::quantize(Point::new(channel(color.x), channel(color.y), channel(color.z)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayTransform, ToneMap};
+    use crate::vector::Point;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn curves() {
+        for tone_map in ToneMap::iter() {
+            assert_eq!(tone_map.apply(0.0), 0.0, "{tone_map}");
+            assert_eq!(tone_map.apply(-1.0), 0.0, "{tone_map}");
+            let mut previous = 0.0;
+            for i in 1..100 {
+                let c = tone_map.apply(i as f32 * 0.1);
+                assert!(c >= previous && c <= 1.0, "{tone_map} is not monotonic");
+                previous = c;
+            }
+        }
+        assert_eq!(ToneMap::Reinhard.apply(1.0), 0.5);
+        assert!(ToneMap::Aces.apply(100.0) > 0.99);
+    }
+
+    #[test]
+    fn exposure_and_srgb() {
+        let linear = DisplayTransform {
+            tone_map: ToneMap::Clamp,
+            exposure: 1.0,
+            srgb: false,
+        };
+        assert_eq!(linear.apply(Point::new(0.25, 0.5, 1.0)), Point::new(128, 255, 255));
+
+        let srgb = DisplayTransform::default();
+        assert_eq!(srgb.apply(Point::homogeneous(0.2158605)), Point::homogeneous(128));
+        assert_eq!(srgb.apply(Point::homogeneous(2.0)), Point::homogeneous(255));
+    }
+}
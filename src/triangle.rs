@@ -16,22 +16,30 @@ pub struct Triangle {
     pub n1: Point<f32>,
     pub n2: Point<f32>,
 
-    pub color: Point<u8>,
+    /// Texture coordinates of p0, p1 and p2
+    pub uvs: Option<[[f32; 2]; 3]>,
+    /// Directions in which u and v increase, None when the texture coordinates are degenerate
+    pub tangents: Option<(Point<f32>, Point<f32>)>,
+
+    /// Index into `Scene.materials`
+    pub material: usize,
 }
 
 impl Triangle {
     // can this be done better?
-    pub fn new(p0: Point<f32>, p1: Point<f32>, p2: Point<f32>, color: Point<u8>) -> Self {
+    pub fn new(p0: Point<f32>, p1: Point<f32>, p2: Point<f32>, material: usize) -> Self {
         let (p0, p1, p2) = make_points_unique(&mut p0.clone(), &mut p1.clone(), &mut p2.clone());
         Self {
             p0,
             p1,
             p2,
-            color,
+            material,
             vertex_normals: false,
             n0: Point::homogeneous(0.0),
             n1: Point::homogeneous(0.0),
             n2: Point::homogeneous(0.0),
+            uvs: None,
+            tangents: None,
         }
     }
 
@@ -42,22 +50,45 @@ impl Triangle {
         n0: Point<f32>,
         n1: Point<f32>,
         n2: Point<f32>,
-        color: Point<u8>,
+        material: usize,
     ) -> Self {
         let (p0, p1, p2) = make_points_unique(&mut p0.clone(), &mut p1.clone(), &mut p2.clone());
         Self {
             p0,
             p1,
             p2,
-            color,
+            material,
             vertex_normals: true,
             n0,
             n1,
             n2,
+            uvs: None,
+            tangents: None,
         }
     }
 
-    #[allow(dead_code)]
+    pub fn with_uvs(self, uvs: [[f32; 2]; 3]) -> Self {
+        Self {
+            uvs: Some(uvs),
+            tangents: self.tangents(uvs),
+            ..self
+        }
+    }
+
+    /// Tangent and bitangent from the edges and their difference in texture coordinates
+    fn tangents(&self, [uv0, uv1, uv2]: [[f32; 2]; 3]) -> Option<(Point<f32>, Point<f32>)> {
+        let (edge1, edge2) = self.edges();
+        let (du1, dv1) = (uv1[0] - uv0[0], uv1[1] - uv0[1]);
+        let (du2, dv2) = (uv2[0] - uv0[0], uv2[1] - uv0[1]);
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let tangent = (edge1 * dv2 - edge2 * dv1) / det;
+        let bitangent = (edge2 * du1 - edge1 * du2) / det;
+        Some((tangent.to_normalized(), bitangent.to_normalized()))
+    }
+
     fn edges(&self) -> (Point<f32>, Point<f32>) {
         let edge1 = self.p1 - self.p0;
         let edge2 = self.p2 - self.p0;
@@ -98,8 +129,24 @@ impl Triangle {
             false => self.normal(),
         };
 
-        let normal = correct_normal(normal, &ray.dir);
-        return Some(Hit::new(t, ray.origin, ray.origin * t, normal, self.color));
+        let (normal, front_face) = correct_normal(normal, &ray.dir);
+        let mut hit = Hit::new(
+            t,
+            ray.origin,
+            ray.origin + ray.dir * t,
+            normal,
+            front_face,
+            self.material,
+        );
+        hit.tangents = self.tangents;
+        hit.uv = self.uvs.map(|[uv0, uv1, uv2]| {
+            let w = 1.0 - u - v;
+            [
+                uv0[0] * w + uv1[0] * u + uv2[0] * v,
+                uv0[1] * w + uv1[1] * u + uv2[1] * v,
+            ]
+        });
+        return Some(hit);
     }
 
     fn barycentric_coordinates(&self, point: Point<f32>) -> (f32, f32) {
@@ -205,7 +252,7 @@ mod tests {
             Point::new(-8.350787, 547.8047, -204.87953),
             Point::new(19.802517, 662.56616, -351.3024),
             Point::new(19.802517, 662.56616, -351.3024),
-            Point::new(0, 0, 0),
+            0,
         );
         let normal = t.normal();
         assert!(normal.x.is_finite());
@@ -213,6 +260,26 @@ mod tests {
         assert!(normal.z.is_finite());
     }
 
+    #[test]
+    fn tangents_follow_the_texture() {
+        let t = Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, 2.0),
+            0,
+        );
+        // u along -z, v along x
+        let (tangent, bitangent) = t
+            .clone()
+            .with_uvs([[1.0, 0.0], [1.0, 1.0], [0.0, 0.0]])
+            .tangents
+            .unwrap();
+        assert_eq!(tangent, Point::new(0.0, 0.0, -1.0));
+        assert_eq!(bitangent, Point::new(1.0, 0.0, 0.0));
+
+        assert!(t.with_uvs([[0.5, 0.5]; 3]).tangents.is_none());
+    }
+
     #[test]
     fn test_make_unique() {
         let mut p0 = Point::new(0.1, 0.0, 0.0);
@@ -1,6 +1,10 @@
 use crate::{octree::AABB, vector::Point};
 use serde::{Deserialize, Serialize};
 use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Hits closer than this are ignored, so rays leaving a surface do not hit that same surface
+pub const RAY_EPSILON: f32 = 1e-4;
 
 pub struct Ray {
     pub origin: Point<f32>,
@@ -18,39 +22,96 @@ pub struct Hit {
     pub dist: f32,
     pub origin: Point<f32>,
     pub point: Point<f32>,
+    /// Faces against the ray
     pub normal: Point<f32>,
-    pub color: Point<u8>,
+    /// The ray hit the outside of the surface, so it enters the shape
+    pub front_face: bool,
+    /// Index into `Scene.materials`
+    pub material: usize,
+    /// Texture coordinate, only shapes with per-vertex UVs have one
+    pub uv: Option<[f32; 2]>,
+    /// Directions in which u and v increase along the surface, for normal mapping
+    pub tangents: Option<(Point<f32>, Point<f32>)>,
 }
 impl Hit {
-    pub fn new(dist: f32, origin: Point<f32>, point: Point<f32>, normal: Point<f32>, color: Point<u8>) -> Self {
+    pub fn new(
+        dist: f32,
+        origin: Point<f32>,
+        point: Point<f32>,
+        normal: Point<f32>,
+        front_face: bool,
+        material: usize,
+    ) -> Self {
         Self {
             dist,
             origin,
             point,
             normal,
-            color,
+            front_face,
+            material,
+            uv: None,
+            tangents: None,
         }
     }
     pub fn replace_if_closer(&mut self, other: Hit) {
         if other.dist < self.dist {
-            self.dist = other.dist;
-            self.origin = other.origin;
-            self.point = other.point;
-            self.normal = other.normal;
-            self.color = other.color;
+            *self = other;
+        }
+    }
+    pub fn closest(a: Option<Hit>, b: Option<Hit>) -> Option<Hit> {
+        match (a, b) {
+            (Some(mut a), Some(b)) => {
+                a.replace_if_closer(b);
+                Some(a)
+            }
+            (a, None) => a,
+            (None, b) => b,
         }
     }
 }
 
-pub fn correct_normal(normal: Point<f32>, dir: &Point<f32>) -> Point<f32> {
+/// Flips the outward `normal` to face against `dir`.
+/// The bool is true when it did not need flipping: the ray hits the outside and enters the shape
+pub fn correct_normal(normal: Point<f32>, dir: &Point<f32>) -> (Point<f32>, bool) {
     let inverse = normal * -1.0;
-    return if normal.dot(&dir) < inverse.dot(&dir) {
-        inverse
+    return if normal.dot(&dir) > inverse.dot(&dir) {
+        (inverse, false)
     } else {
-        normal
+        (normal, true)
     };
 }
 
+/// Moves a point off the surface along its normal, scaled with the magnitude of the coordinates,
+/// so a ray leaving the surface does not hit that same surface because of floating point errors
+pub fn offset_from_surface(point: Point<f32>, normal: Point<f32>) -> Point<f32> {
+    let scale = point.x.abs().max(point.y.abs()).max(point.z.abs()).max(1.0);
+    return point + normal * (RAY_EPSILON * scale);
+}
+
+/// Mirrors `dir` around `normal`, both normalized
+pub fn reflect(dir: Point<f32>, normal: Point<f32>) -> Point<f32> {
+    return dir - normal * (2.0 * dir.dot(&normal));
+}
+
+/// Bends `dir` with Snell's law through a surface whose `normal` faces against it, `eta` is n1 / n2.
+/// None on total internal reflection
+pub fn refract(dir: Point<f32>, normal: Point<f32>, eta: f32) -> Option<Point<f32>> {
+    let cos_i = -dir.dot(&normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    return Some((dir * eta + normal * (eta * cos_i - cos_t)).to_normalized());
+}
+
+/// Schlick's approximation of the fraction of light a surface between media `n1` and `n2` reflects.
+/// `cos` is the cosine of the angle on the side of the optically thinner medium
+pub fn schlick(cos: f32, n1: f32, n2: f32) -> f32 {
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    return r0 + (1.0 - r0) * (1.0 - cos).powi(5);
+}
+
 pub trait Intersect {
     fn hit(&self, ray: &Ray) -> Option<Hit>;
 }
@@ -69,30 +130,34 @@ pub fn fps_to_duration(fps: u32) -> std::time::Duration {
 pub struct PixelRes {
     pub x: usize,
     pub y: usize,
-    pub color: Point<u8>,
+    pub camera: usize,
+    /// Linear, see `color`
+    pub color: Point<f32>,
 }
 
 impl PixelRes {
-    pub fn new(x: usize, y: usize, color: Point<u8>) -> Self {
-        Self { x, y, color }
+    pub fn new(x: usize, y: usize, camera: usize, color: Point<f32>) -> Self {
+        Self { x, y, camera, color }
     }
 }
 
+/// `camera` is an index into `Scene.cameras`
 #[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 pub struct PixelReq {
     pub x: usize,
     pub y: usize,
+    pub camera: usize,
 }
 
 impl PartialEq for PixelReq {
     fn eq(&self, other: &Self) -> bool {
-        self.x == other.x && self.y == other.y
+        self.x == other.x && self.y == other.y && self.camera == other.camera
     }
 }
 
 impl PixelReq {
-    pub fn new(x: usize, y: usize) -> Self {
-        Self { x, y }
+    pub fn new(x: usize, y: usize, camera: usize) -> Self {
+        Self { x, y, camera }
     }
 }
 
@@ -102,6 +167,7 @@ pub type PixelResBuffer = [Option<PixelRes>; PIXEL_BUFFER_SIZE];
 
 pub trait ExitOnError<T> {
     fn exit_with(self, message: &str) -> T;
+    fn exit_with_error(self) -> T;
 }
 
 impl<T, E: std::fmt::Display> ExitOnError<T> for Result<T, E> {
@@ -114,9 +180,29 @@ impl<T, E: std::fmt::Display> ExitOnError<T> for Result<T, E> {
             }
         }
     }
+    fn exit_with_error(self) -> T {
+        match self {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Set with `--threads`, 0 means one thread per core
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_threads(threads: NonZeroUsize) {
+    THREADS.store(threads.get(), Ordering::Relaxed);
 }
 
 pub fn threads() -> usize {
+    let threads = THREADS.load(Ordering::Relaxed);
+    if threads != 0 {
+        return threads;
+    }
     return std::thread::available_parallelism()
         .unwrap_or(NonZeroUsize::new(8).unwrap())
         .get();
@@ -147,7 +233,7 @@ mod split_test {
         let mut buf = [None; X];
 
         for i in 0..X {
-            buf[i] = Some(PixelReq::new(i, i));
+            buf[i] = Some(PixelReq::new(i, i, 0));
         }
 
         for split_size in vec![1, 2, 3, 4, X - 2, X - 1, X, X + 1, X + 2] {
@@ -174,3 +260,37 @@ mod split_test {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{reflect, refract, schlick};
+    use crate::vector::Point;
+
+    #[test]
+    fn test_reflect() {
+        let dir = Point::new(1.0, -1.0, 0.0).to_normalized();
+        let reflected = reflect(dir, Point::new(0.0, 1.0, 0.0));
+        assert!(reflected.approx_eq(&Point::new(1.0, 1.0, 0.0).to_normalized(), 1e-6));
+    }
+
+    #[test]
+    fn test_refract() {
+        let normal = Point::new(0.0, 1.0, 0.0);
+        let straight = Point::new(0.0, -1.0, 0.0);
+        assert!(refract(straight, normal, 1.0 / 1.5).unwrap().approx_eq(&straight, 1e-6));
+
+        // 45 degrees into glass bends towards the normal: sin(t) = sin(45) / 1.5
+        let dir = Point::new(1.0, -1.0, 0.0).to_normalized();
+        let refracted = refract(dir, normal, 1.0 / 1.5).unwrap();
+        assert!((refracted.x - 0.70710677 / 1.5).abs() < 1e-5);
+
+        // leaving glass at 45 degrees is past the critical angle of 41.8
+        assert!(refract(dir, normal, 1.5).is_none());
+    }
+
+    #[test]
+    fn test_schlick() {
+        assert!((schlick(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert_eq!(schlick(0.0, 1.0, 1.5), 1.0);
+    }
+}
@@ -1,55 +1,171 @@
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, TryRecvError};
 use std::sync::Arc;
-use std::sync::RwLock;
 use std::time;
 
-use minifb::Key;
+use minifb::{Key, KeyRepeat};
 
-use crate::resolution::Resolution;
-use crate::scene_readers;
+use crate::camera::Camera;
+use crate::frame_buffer::{FrameBuffer, PixelProvider};
+use crate::renderer::render_multithreaded;
+use crate::resolution::{AALevel, Resolution};
+use crate::scene_readers::Scene;
+use crate::tone_map::DisplayTransform;
+use crate::util::PixelResBuffer;
+
+/// Every pass renders one pixel per `scale * scale` block of the window, the last pass renders every pixel
+const PASS_SCALES: [usize; 5] = [16, 8, 4, 2, 1];
+
+/// One pass of the progressive render
+struct Pass {
+    /// Index into `PASS_SCALES`
+    index: usize,
+    pixels: Receiver<PixelResBuffer>,
+}
+
+impl Pass {
+    fn scale(&self) -> usize {
+        PASS_SCALES[self.index]
+    }
+}
+
+pub fn loop_until_closed(
+    scene: Arc<Scene>,
+    resolution: Resolution,
+    mut active_camera: usize,
+    seed: u64,
+    display: DisplayTransform,
+    save_path: PathBuf,
+) -> Result<(), String> {
+    let mut window = get_window(&resolution)?;
+    let mut frame_buffer = FrameBuffer::new(&resolution)?;
+    let mut cameras: Vec<Camera> = scene.cameras.clone();
+    let mut pass = Some(start_pass(0, &scene, &cameras, active_camera, &resolution, seed));
 
-pub fn loop_until_closed(scene: Arc<RwLock<scene_readers::Scene>>, resolution: Resolution) {
-    let window = get_window(&resolution);
-    let mut pressed = true;
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        let mut moved = false;
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            active_camera = (active_camera + 1) % cameras.len();
+            println!("Camera {} of {}", active_camera + 1, cameras.len());
+            moved = true;
+        }
+        if window.is_key_pressed(Key::E, KeyRepeat::No) {
+            let mut saved = (*scene).clone();
+            saved.cameras = cameras.clone();
+            // a failed save should not close the window
+            match saved.save_to_file(&save_path) {
+                Ok(file_size) => println!("Scene saved to {:?} ({})", save_path, file_size),
+                Err(e) => println!("WARN: could not save the scene to {:?}: {}", save_path, e),
+            }
+        }
         for key in window.get_keys() {
-            pressed = pressed || scene.write().unwrap().camera.keyboard(&key);
-            if key == Key::E {
-                pressed = true;
-                let path = PathBuf::from("scene.cbor");
-                let file_size = scene.read().unwrap().save_to_file(&path).unwrap();
-                println!("Scene saved to {:?} ({})", path, file_size);
+            moved |= cameras[active_camera].keyboard(&key);
+        }
+        if moved {
+            // dropping the old pass stops its threads
+            pass = Some(start_pass(0, &scene, &cameras, active_camera, &resolution, seed));
+        }
+
+        if let Some(current) = &pass {
+            let finished = receive_pixels(current, &mut frame_buffer, &resolution);
+            if finished {
+                let next = current.index + 1;
+                pass = match next < PASS_SCALES.len() {
+                    true => Some(start_pass(next, &scene, &cameras, active_camera, &resolution, seed)),
+                    false => None,
+                };
             }
         }
-        // if pressed {
-        //     renderer.render(&scene, false);
-        //     let fb = renderer.frame_buffer.lock().unwrap();
-        //     window
-        //         .update_with_buffer(&fb.buffer(), resolution.width.get(), resolution.height.get())
-        //         .unwrap();
-        // } else {
-        //     window.update();
-        //     std::thread::sleep(Duration::from_micros(16600));
-        // }
-        pressed = false;
+        window
+            .update_with_buffer(
+                &frame_buffer.to_u32_buffer(&display),
+                resolution.width.get(),
+                resolution.height.get(),
+            )
+            .map_err(|e| e.to_string())?;
     }
+    Ok(())
 }
 
-fn get_window(resolution: &Resolution) -> minifb::Window {
+/// Renders the scene at `resolution` divided by the scale of the pass, anti-aliasing is left for the last pass
+fn start_pass(
+    index: usize,
+    scene: &Arc<Scene>,
+    cameras: &[Camera],
+    camera: usize,
+    resolution: &Resolution,
+    seed: u64,
+) -> Pass {
+    let scale = PASS_SCALES[index];
+    let scaled = |size: NonZeroUsize| NonZeroUsize::new(size.get().div_ceil(scale)).unwrap();
+    let aa = match scale {
+        1 => resolution.aa,
+        _ => AALevel::new(1).unwrap(),
+    };
+    let pass_resolution = Resolution::new(scaled(resolution.width), scaled(resolution.height), aa);
+    let pixel_provider = PixelProvider::new(&pass_resolution, vec![camera], seed);
+    Pass {
+        index,
+        pixels: render_multithreaded(scene.clone(), cameras, &pass_resolution, pixel_provider),
+    }
+}
+
+/// Paints the pixels rendered so far as blocks of `scale * scale`, returns true when the pass is done
+fn receive_pixels(pass: &Pass, frame_buffer: &mut FrameBuffer, resolution: &Resolution) -> bool {
+    let scale = pass.scale();
+    loop {
+        let buffer = match pass.pixels.try_recv() {
+            Ok(buffer) => buffer,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => return true,
+        };
+        for pixel in buffer.iter().flatten() {
+            for y in pixel.y * scale..((pixel.y + 1) * scale).min(resolution.height.get()) {
+                for x in pixel.x * scale..((pixel.x + 1) * scale).min(resolution.width.get()) {
+                    frame_buffer.set_pixel(x, y, pixel.color);
+                }
+            }
+        }
+    }
+}
+
+fn get_window(resolution: &Resolution) -> Result<minifb::Window, String> {
     let mut window = minifb::Window::new(
-        "Test - ESC to exit",
+        "miniRT - ESC to exit, C to switch camera, E to save the scene",
         resolution.width.get(),
         resolution.height.get(),
         minifb::WindowOptions::default(),
     )
-    .unwrap();
-    window
-        .update_with_buffer(
-            &vec![0; resolution.width.get() * resolution.height.get()],
-            resolution.width.get(),
-            resolution.height.get(),
-        )
-        .unwrap();
+    .map_err(|e| format!("Failed to open a window: {}", e))?;
     window.limit_update_rate(Some(time::Duration::from_micros(16600)));
-    window
+    Ok(window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{receive_pixels, start_pass, PASS_SCALES};
+    use crate::frame_buffer::FrameBuffer;
+    use crate::resolution::{AALevel, Resolution};
+    use crate::scene_readers::{read_scene, ParseMode};
+    use std::num::NonZeroUsize;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn every_pass_covers_the_whole_window() {
+        let scene = Arc::new(read_scene(Path::new("rt_test/basic_triangle.rt"), ParseMode::Strict).unwrap());
+        // not a multiple of any scale, so the last blocks are cut off
+        let resolution = Resolution::new(
+            NonZeroUsize::new(37).unwrap(),
+            NonZeroUsize::new(21).unwrap(),
+            AALevel::new(1).unwrap(),
+        );
+        for index in 0..PASS_SCALES.len() {
+            let mut frame_buffer = FrameBuffer::new(&resolution).unwrap();
+            let pass = start_pass(index, &scene, &scene.cameras, 0, &resolution, 0);
+            while !receive_pixels(&pass, &mut frame_buffer, &resolution) {}
+            assert!(frame_buffer.is_complete(), "pass {index}");
+        }
+    }
 }